@@ -0,0 +1,867 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::cpu::Variant;
+use crate::opcode::{AddressingMode, Mnemonic, Opcode};
+
+/// The output of `assemble`: the bytes from `origin` up to the end of the
+/// last statement, and the address or value of every label and constant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+    pub symbols: BTreeMap<String, u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmError {
+    /// The line could not be parsed.
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// The mnemonic is not an instruction of the chip being assembled for.
+    UnknownMnemonic {
+        line: usize,
+        mnemonic: String,
+    },
+    /// The instruction has no opcode for the operand it was given.
+    InvalidOperand {
+        line: usize,
+        mnemonic: Mnemonic,
+    },
+    UndefinedSymbol {
+        line: usize,
+        name: String,
+    },
+    DuplicateSymbol {
+        line: usize,
+        name: String,
+    },
+    /// `value` does not fit in the byte or word it is assembled into.
+    ValueOutOfRange {
+        line: usize,
+        value: i64,
+    },
+    /// The branch target is `offset` bytes away, more than a signed byte.
+    BranchOutOfRange {
+        line: usize,
+        offset: i64,
+    },
+    /// `.org` went back below code that was already assembled.
+    OrgBackwards {
+        line: usize,
+        address: u16,
+    },
+    /// The line would run past 0xFFFF.
+    AddressOverflow {
+        line: usize,
+    },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsmError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            AsmError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "line {}: unknown mnemonic {}", line, mnemonic)
+            }
+            AsmError::InvalidOperand { line, mnemonic } => {
+                write!(f, "line {}: invalid operand for {}", line, mnemonic)
+            }
+            AsmError::UndefinedSymbol { line, name } => {
+                write!(f, "line {}: undefined symbol {}", line, name)
+            }
+            AsmError::DuplicateSymbol { line, name } => {
+                write!(f, "line {}: {} is already defined", line, name)
+            }
+            AsmError::ValueOutOfRange { line, value } => {
+                write!(f, "line {}: value {} is out of range", line, value)
+            }
+            AsmError::BranchOutOfRange { line, offset } => {
+                write!(f, "line {}: branch offset {} is out of range", line, offset)
+            }
+            AsmError::OrgBackwards { line, address } => write!(
+                f,
+                "line {}: .org 0x{:04X} is below code already assembled",
+                line, address
+            ),
+            AsmError::AddressOverflow { line } => {
+                write!(f, "line {}: code runs past 0xFFFF", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Assembles `source` for the NMOS 6502 and the NES's 2A03.
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    assemble_for(source, Variant::default())
+}
+
+/// Assembles `source` with the instruction set of `variant`.
+///
+/// The syntax is the common 6502 one, one statement per line:
+///
+/// ```text
+/// COUNT = 5           ; a constant
+///         .org $0600
+/// start:  ldx #COUNT  ; labels end in a colon
+/// loop:   dex
+///         bne loop
+///         lda table,x
+///         jmp (vector)
+/// table:  .byte 1, 2, $03, %100, 'A', "text"
+/// vector: .word start, *+2
+/// ```
+///
+/// Mnemonics, directives and register names are case-insensitive; symbols
+/// are not. Expressions take `$hex`, `%binary`, decimal and `'c'` numbers,
+/// symbols and `*` for the address of the current statement, combined
+/// with `+ - * / & | ^` and the prefixes `-`, `<` (low byte) and `>` (high
+/// byte). There are no parentheses, which would read as indirection.
+/// Unofficial opcodes assemble under the names `Mnemonic` gives them.
+///
+/// Labels and constants can both be used before they are defined. Such an
+/// operand is assembled as absolute even if it turns out to be on the zero
+/// page, unless the instruction only has a zero page form, as `STX zp,Y`
+/// does. The value of a constant is worked out where it is defined, so it
+/// can only refer to symbols defined above it.
+pub fn assemble_for(source: &str, variant: Variant) -> Result<Assembly, AsmError> {
+    let mut symbols = BTreeMap::new();
+    let mut statements = Vec::new();
+    let mut origin = None;
+    let mut pc: u32 = 0;
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let syntax = |message: String| AsmError::Syntax { line, message };
+        let mut rest = strip_comment(text).trim();
+
+        if let Some((name, after)) = split_label(rest) {
+            define(&mut symbols, line, name, pc as u16)?;
+            rest = after.trim();
+        }
+        if rest.is_empty() {
+            continue;
+        }
+
+        let equate = rest
+            .split_once('=')
+            .filter(|(name, _)| is_symbol(name.trim()));
+        if let Some((name, value)) = equate {
+            let name = name.trim();
+            let value = evaluate(value, &symbols, pc as u16, line)?;
+            let value = to_word(value, line)?;
+            define(&mut symbols, line, name, value)?;
+            continue;
+        }
+
+        let (word, operand) = match rest.find(char::is_whitespace) {
+            Some(end) => (&rest[..end], rest[end..].trim()),
+            None => (rest, ""),
+        };
+
+        let kind = match word.to_ascii_lowercase().as_str() {
+            ".org" => {
+                let address = evaluate(operand, &symbols, pc as u16, line)?;
+                let address = to_word(address, line)?;
+                match origin {
+                    None => origin = Some(address),
+                    Some(_) if (address as u32) < pc => {
+                        return Err(AsmError::OrgBackwards { line, address })
+                    }
+                    Some(_) => {}
+                }
+                pc = address as u32;
+                continue;
+            }
+            ".byte" | ".db" => Kind::Bytes(split_arguments(operand, line)?),
+            ".word" | ".dw" => Kind::Words(split_arguments(operand, line)?),
+            directive if directive.starts_with('.') => {
+                return Err(syntax(format!("unknown directive {}", word)));
+            }
+            _ => {
+                let mnemonic =
+                    parse_mnemonic(word, variant).ok_or_else(|| AsmError::UnknownMnemonic {
+                        line,
+                        mnemonic: word.to_string(),
+                    })?;
+                let (mode, expr) =
+                    addressing_mode(mnemonic, operand, variant, &symbols, pc as u16, line)?;
+                let code = encoding(mnemonic, mode, variant)
+                    .ok_or(AsmError::InvalidOperand { line, mnemonic })?;
+                Kind::Instruction { code, mode, expr }
+            }
+        };
+
+        origin.get_or_insert(pc as u16);
+        let statement = Statement {
+            line,
+            address: pc as u16,
+            kind,
+        };
+        pc += statement.len() as u32;
+        if pc > 0x10000 {
+            return Err(AsmError::AddressOverflow { line });
+        }
+        statements.push(statement);
+    }
+
+    let origin = origin.unwrap_or(0);
+    let mut bytes = Vec::new();
+    for statement in &statements {
+        let line = statement.line;
+        // A gap left by `.org` is filled with zeros.
+        bytes.resize((statement.address - origin) as usize, 0);
+        match &statement.kind {
+            Kind::Instruction { code, mode, expr } => {
+                bytes.push(*code);
+                let Some(expr) = expr else {
+                    continue;
+                };
+                let value = evaluate(expr, &symbols, statement.address, line)?;
+                match mode {
+                    AddressingMode::Relative => {
+                        let offset = value - (statement.address as i64 + 2);
+                        if !(-128..=127).contains(&offset) {
+                            return Err(AsmError::BranchOutOfRange { line, offset });
+                        }
+                        bytes.push(offset as u8);
+                    }
+                    mode if mode.operand_len() == 1 => bytes.push(to_byte(value, line)?),
+                    _ => bytes.extend_from_slice(&to_word(value, line)?.to_le_bytes()),
+                }
+            }
+            Kind::Bytes(arguments) => {
+                for argument in arguments {
+                    match argument {
+                        Argument::String(text) => bytes.extend_from_slice(text.as_bytes()),
+                        Argument::Expr(expr) => {
+                            let value = evaluate(expr, &symbols, statement.address, line)?;
+                            bytes.push(to_byte(value, line)?);
+                        }
+                    }
+                }
+            }
+            Kind::Words(arguments) => {
+                for argument in arguments {
+                    let Argument::Expr(expr) = argument else {
+                        return Err(AsmError::Syntax {
+                            line,
+                            message: "strings are only allowed in .byte".to_string(),
+                        });
+                    };
+                    let value = evaluate(expr, &symbols, statement.address, line)?;
+                    bytes.extend_from_slice(&to_word(value, line)?.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    Ok(Assembly {
+        origin,
+        bytes,
+        symbols,
+    })
+}
+
+struct Statement {
+    line: usize,
+    address: u16,
+    kind: Kind,
+}
+
+enum Kind {
+    Instruction {
+        code: u8,
+        mode: AddressingMode,
+        expr: Option<String>,
+    },
+    Bytes(Vec<Argument>),
+    Words(Vec<Argument>),
+}
+
+enum Argument {
+    Expr(String),
+    String(String),
+}
+
+impl Statement {
+    fn len(&self) -> usize {
+        match &self.kind {
+            Kind::Instruction { mode, .. } => 1 + mode.operand_len() as usize,
+            Kind::Bytes(arguments) => arguments
+                .iter()
+                .map(|argument| match argument {
+                    Argument::String(text) => text.len(),
+                    Argument::Expr(_) => 1,
+                })
+                .sum(),
+            Kind::Words(arguments) => 2 * arguments.len(),
+        }
+    }
+}
+
+fn define(
+    symbols: &mut BTreeMap<String, u16>,
+    line: usize,
+    name: &str,
+    value: u16,
+) -> Result<(), AsmError> {
+    if symbols.insert(name.to_string(), value).is_some() {
+        return Err(AsmError::DuplicateSymbol {
+            line,
+            name: name.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Drops everything from a `;` that is not inside quotes.
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, ';') => return &text[..i],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Removes whitespace outside quotes, so `$10 , x` reads as `$10,x`.
+fn compact(text: &str) -> String {
+    let mut quote = None;
+    text.chars()
+        .filter(|&c| {
+            match (quote, c) {
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(open), _) if c == open => quote = None,
+                _ => {}
+            }
+            quote.is_some() || !c.is_whitespace()
+        })
+        .collect()
+}
+
+/// Splits `name:` off the start of a line.
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let (name, rest) = text.split_once(':')?;
+    is_symbol(name).then_some((name, rest))
+}
+
+fn is_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Splits the arguments of `.byte` and `.word` on commas outside quotes.
+fn split_arguments(text: &str, line: usize) -> Result<Vec<Argument>, AsmError> {
+    let mut arguments = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in text.char_indices().chain([(text.len(), ',')]) {
+        match (quote, c) {
+            (None, ',') => {
+                let argument = text[start..i].trim();
+                if argument.is_empty() {
+                    return Err(AsmError::Syntax {
+                        line,
+                        message: "missing argument".to_string(),
+                    });
+                }
+                arguments.push(match argument.strip_prefix('"') {
+                    Some(rest) => Argument::String(rest.trim_end_matches('"').to_string()),
+                    None => Argument::Expr(argument.to_string()),
+                });
+                start = i + 1;
+            }
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            _ => {}
+        }
+    }
+    if quote.is_some() {
+        return Err(AsmError::Syntax {
+            line,
+            message: "unterminated string".to_string(),
+        });
+    }
+    Ok(arguments)
+}
+
+fn parse_mnemonic(word: &str, variant: Variant) -> Option<Mnemonic> {
+    let word = word.to_ascii_uppercase();
+    (0..=255)
+        .map(|code| Opcode::decode(code, variant).mnemonic)
+        .find(|mnemonic| mnemonic.to_string() == word)
+}
+
+/// The opcode for `mnemonic` in `mode`. Official opcodes are preferred,
+/// so `NOP` is 0xEA and `SBC #` is 0xE9.
+fn encoding(mnemonic: Mnemonic, mode: AddressingMode, variant: Variant) -> Option<u8> {
+    let opcodes = (0..=255)
+        .map(|code| Opcode::decode(code, variant))
+        .filter(|opcode| opcode.mnemonic == mnemonic && opcode.mode == mode);
+    opcodes
+        .clone()
+        .find(|opcode| opcode.official)
+        .or_else(|| opcodes.clone().next())
+        .map(|opcode| opcode.code)
+}
+
+/// Works out the addressing mode from the shape of the operand, and
+/// returns it with the expression inside.
+fn addressing_mode(
+    mnemonic: Mnemonic,
+    operand: &str,
+    variant: Variant,
+    symbols: &BTreeMap<String, u16>,
+    pc: u16,
+    line: usize,
+) -> Result<(AddressingMode, Option<String>), AsmError> {
+    use AddressingMode::*;
+
+    let operand = compact(operand);
+    let operand = operand.as_str();
+    let has = |mode| encoding(mnemonic, mode, variant).is_some();
+    let upper = operand.to_ascii_uppercase();
+    let inner = |prefix: usize, suffix: usize| &operand[prefix..operand.len() - suffix];
+    // The zero page form is only chosen when the value is already known
+    // to fit, so the size cannot change once later labels are defined.
+    let on_zero_page = |expr: &str| matches!(evaluate(expr, symbols, pc, line), Ok(value) if (0..=0xFF).contains(&value));
+    let sized = |expr: &str, zero_page, absolute| {
+        if has(zero_page) && (!has(absolute) || on_zero_page(expr)) {
+            zero_page
+        } else {
+            absolute
+        }
+    };
+
+    let (mode, expr) = if operand.is_empty() {
+        (if has(Implied) { Implied } else { Accumulator }, None)
+    } else if upper == "A" {
+        (Accumulator, None)
+    } else if let Some(expr) = operand.strip_prefix('#') {
+        (Immediate, Some(expr))
+    } else if upper.starts_with('(') && upper.ends_with(",X)") {
+        let expr = inner(1, 3);
+        (sized(expr, IndirectX, AbsoluteIndirectX), Some(expr))
+    } else if upper.starts_with('(') && upper.ends_with("),Y") {
+        (IndirectY, Some(inner(1, 3)))
+    } else if upper.starts_with('(') && upper.ends_with(')') {
+        let expr = inner(1, 1);
+        (sized(expr, ZeroPageIndirect, Indirect), Some(expr))
+    } else if upper.ends_with(",X") {
+        let expr = inner(0, 2);
+        (sized(expr, ZeroPageX, AbsoluteX), Some(expr))
+    } else if upper.ends_with(",Y") {
+        let expr = inner(0, 2);
+        (sized(expr, ZeroPageY, AbsoluteY), Some(expr))
+    } else if has(Relative) {
+        (Relative, Some(operand))
+    } else {
+        (sized(operand, ZeroPage, Absolute), Some(operand))
+    };
+
+    if expr.is_some_and(str::is_empty) {
+        return Err(AsmError::Syntax {
+            line,
+            message: format!("missing operand for {}", mnemonic),
+        });
+    }
+    Ok((mode, expr.map(str::to_string)))
+}
+
+fn to_byte(value: i64, line: usize) -> Result<u8, AsmError> {
+    match value {
+        -0x80..=0xFF => Ok(value as u8),
+        _ => Err(AsmError::ValueOutOfRange { line, value }),
+    }
+}
+
+fn to_word(value: i64, line: usize) -> Result<u16, AsmError> {
+    match value {
+        -0x8000..=0xFFFF => Ok(value as u16),
+        _ => Err(AsmError::ValueOutOfRange { line, value }),
+    }
+}
+
+/// Evaluates an expression, with `*` standing for `pc`.
+fn evaluate(
+    text: &str,
+    symbols: &BTreeMap<String, u16>,
+    pc: u16,
+    line: usize,
+) -> Result<i64, AsmError> {
+    let mut parser = ExprParser {
+        text: text.as_bytes(),
+        pos: 0,
+        symbols,
+        pc,
+        line,
+    };
+    let value = parser.binary(0)?;
+    parser.skip_whitespace();
+    if parser.pos < parser.text.len() {
+        return Err(parser.error("unexpected characters"));
+    }
+    Ok(value)
+}
+
+/// Binary operators from loosest to tightest binding.
+const PRECEDENCE: [&[u8]; 5] = [b"|", b"^", b"&", b"+-", b"*/"];
+
+struct ExprParser<'a> {
+    text: &'a [u8],
+    pos: usize,
+    symbols: &'a BTreeMap<String, u16>,
+    pc: u16,
+    line: usize,
+}
+
+impl ExprParser<'_> {
+    fn error(&self, message: &str) -> AsmError {
+        AsmError::Syntax {
+            line: self.line,
+            message: format!(
+                "{} in expression {}",
+                message,
+                String::from_utf8_lossy(self.text).trim()
+            ),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.text.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.text.get(self.pos).copied()
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i64, AsmError> {
+        if level == PRECEDENCE.len() {
+            return self.unary();
+        }
+        let mut value = self.binary(level + 1)?;
+        while let Some(op) = self.peek().filter(|op| PRECEDENCE[level].contains(op)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            value = match op {
+                b'|' => value | rhs,
+                b'^' => value ^ rhs,
+                b'&' => value & rhs,
+                b'+' => value + rhs,
+                b'-' => value - rhs,
+                b'*' => value * rhs,
+                _ if rhs == 0 => return Err(self.error("division by zero")),
+                _ => value / rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i64, AsmError> {
+        match self.peek() {
+            Some(b'-') => {
+                self.pos += 1;
+                Ok(-self.unary()?)
+            }
+            Some(b'<') => {
+                self.pos += 1;
+                Ok(self.unary()? & 0xFF)
+            }
+            Some(b'>') => {
+                self.pos += 1;
+                Ok((self.unary()? >> 8) & 0xFF)
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<i64, AsmError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let digits = |parser: &mut Self, radix: u32| {
+            let from = parser.pos;
+            while parser
+                .text
+                .get(parser.pos)
+                .is_some_and(|c| (*c as char).is_digit(radix))
+            {
+                parser.pos += 1;
+            }
+            let digits = std::str::from_utf8(&parser.text[from..parser.pos]).unwrap();
+            i64::from_str_radix(digits, radix).map_err(|_| parser.error("invalid number"))
+        };
+
+        match self.peek() {
+            Some(b'*') => {
+                self.pos += 1;
+                Ok(self.pc as i64)
+            }
+            Some(b'$') => {
+                self.pos += 1;
+                digits(self, 16)
+            }
+            Some(b'%') => {
+                self.pos += 1;
+                digits(self, 2)
+            }
+            Some(c) if c.is_ascii_digit() => digits(self, 10),
+            Some(b'\'') => match self.text.get(self.pos + 1..self.pos + 3) {
+                Some([c, b'\'']) => {
+                    self.pos += 3;
+                    Ok(*c as i64)
+                }
+                _ => Err(self.error("invalid character literal")),
+            },
+            Some(c) if c.is_ascii_alphabetic() || c == b'_' => {
+                while self
+                    .text
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_')
+                {
+                    self.pos += 1;
+                }
+                let name = std::str::from_utf8(&self.text[start..self.pos]).unwrap();
+                match self.symbols.get(name) {
+                    Some(value) => Ok(*value as i64),
+                    None => Err(AsmError::UndefinedSymbol {
+                        line: self.line,
+                        name: name.to_string(),
+                    }),
+                }
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn bytes(source: &str) -> Vec<u8> {
+        assemble(source).unwrap().bytes
+    }
+
+    #[test]
+    fn test_every_addressing_mode() {
+        let source = "
+            nop
+            asl a
+            lsr
+            lda #$10
+            lda $80
+            lda $80,x
+            ldx $80,y
+            lda $1234
+            lda $1234,x
+            lda $1234,y
+            jmp ($FFFC)
+            lda ($80,x)
+            lda ($80),y
+        ";
+
+        assert_eq!(
+            bytes(source),
+            vec![
+                0xEA, 0x0A, 0x4A, 0xA9, 0x10, 0xA5, 0x80, 0xB5, 0x80, 0xB6, 0x80, 0xAD, 0x34, 0x12,
+                0xBD, 0x34, 0x12, 0xB9, 0x34, 0x12, 0x6C, 0xFC, 0xFF, 0xA1, 0x80, 0xB1, 0x80,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labels_branches_and_forward_references() {
+        let assembly = assemble(
+            "
+                    .org $0600
+            start:  ldx #5      ; count down
+            loop:   dex
+                    bne loop
+                    beq done
+                    jmp start
+            done:   rts
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(assembly.origin, 0x0600);
+        assert_eq!(
+            assembly.bytes,
+            vec![0xA2, 0x05, 0xCA, 0xD0, 0xFD, 0xF0, 0x03, 0x4C, 0x00, 0x06, 0x60]
+        );
+        assert_eq!(
+            assembly.symbols.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("done".to_string(), 0x060A),
+                ("loop".to_string(), 0x0602),
+                ("start".to_string(), 0x0600),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forward_reference_is_assembled_absolute() {
+        assert_eq!(
+            bytes("lda later\nlater: .byte 0"),
+            vec![0xAD, 0x03, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_data_directives() {
+        let source = r#"
+            .org $8000
+            table: .byte 1, $02, %11, 'A', "hi;", -1
+            .word table, $1234
+            .org $8010
+            .db >table, <table, '='
+        "#;
+
+        let mut expected = vec![
+            0x01, 0x02, 0x03, 0x41, b'h', b'i', b';', 0xFF, 0x00, 0x80, 0x34, 0x12,
+        ];
+        expected.resize(0x10, 0);
+        expected.extend_from_slice(&[0x80, 0x00, b'=']);
+        assert_eq!(bytes(source), expected);
+    }
+
+    #[test]
+    fn test_expressions_and_constants() {
+        let source = "
+            SCREEN = $0200
+            WIDTH = 32
+            .org $C000
+            lda #WIDTH * 2 + 1
+            sta SCREEN + WIDTH - 1, x
+            lda #<SCREEN | 7
+            ldx #'z' - 'a'
+            jmp * + 3
+        ";
+
+        assert_eq!(
+            bytes(source),
+            vec![0xA9, 0x41, 0x9D, 0x1F, 0x02, 0xA9, 0x07, 0xA2, 0x19, 0x4C, 0x0C, 0xC0,]
+        );
+    }
+
+    #[test]
+    fn test_65c02_and_unofficial_opcodes() {
+        let assembly = assemble_for("bra *\nlda ($80)\njmp ($2000,x)", Variant::Cmos65C02);
+        assert_eq!(
+            assembly.unwrap().bytes,
+            vec![0x80, 0xFE, 0xB2, 0x80, 0x7C, 0x00, 0x20]
+        );
+
+        assert_eq!(
+            bytes("lax $10\nsbc #1\ndcp $10,x"),
+            vec![0xA7, 0x10, 0xE9, 0x01, 0xD7, 0x10]
+        );
+        assert_eq!(
+            assemble("bra *"),
+            Err(AsmError::UnknownMnemonic {
+                line: 1,
+                mnemonic: "bra".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_forward_constant_is_assembled_absolute() {
+        assert_eq!(bytes("lda later\nlater = $10"), vec![0xAD, 0x10, 0x00]);
+        assert_eq!(bytes("later = $10\nlda later"), vec![0xA5, 0x10]);
+    }
+
+    #[test]
+    fn test_zero_page_only_modes_stay_on_the_zero_page() {
+        assert_eq!(bytes("stx later,y\nlater = $10"), vec![0x96, 0x10]);
+    }
+
+    #[test]
+    fn test_constant_value_cannot_refer_forward() {
+        assert_eq!(
+            assemble("early = later + 1\nlater = 2"),
+            Err(AsmError::UndefinedSymbol {
+                line: 1,
+                name: "later".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_unterminated_string_is_an_error() {
+        assert_eq!(
+            assemble("nop\n.byte \"abc"),
+            Err(AsmError::Syntax {
+                line: 2,
+                message: "unterminated string".to_string()
+            })
+        );
+        assert!(assemble(".byte 'a").is_err());
+    }
+
+    #[test]
+    fn test_errors_carry_the_line() {
+        assert_eq!(
+            assemble("nop\nbeq far\n.org $0100\nfar: rts"),
+            Err(AsmError::BranchOutOfRange {
+                line: 2,
+                offset: 0xFD
+            })
+        );
+        assert_eq!(
+            assemble("jmp nowhere"),
+            Err(AsmError::UndefinedSymbol {
+                line: 1,
+                name: "nowhere".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("a: nop\na: nop"),
+            Err(AsmError::DuplicateSymbol {
+                line: 2,
+                name: "a".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("lda #256"),
+            Err(AsmError::ValueOutOfRange {
+                line: 1,
+                value: 256
+            })
+        );
+        assert_eq!(
+            assemble("stx $1234,x"),
+            Err(AsmError::InvalidOperand {
+                line: 1,
+                mnemonic: Mnemonic::STX
+            })
+        );
+        assert_eq!(
+            assemble(".org $0200\nnop\n.org $0100"),
+            Err(AsmError::OrgBackwards {
+                line: 3,
+                address: 0x0100
+            })
+        );
+        assert_eq!(
+            assemble(".org $FFFF\nnop\nnop"),
+            Err(AsmError::AddressOverflow { line: 3 })
+        );
+    }
+}
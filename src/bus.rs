@@ -0,0 +1,246 @@
+#![allow(dead_code)]
+
+/// Everything the CPU can see at the end of its address lines.
+///
+/// `read` may have side effects (PPU status reads clear VBlank, controller
+/// reads shift the latch), so debuggers and trace loggers use `peek` instead.
+/// `tick` is called with the number of CPU cycles that have elapsed so other
+/// chips on the bus can be kept in step.
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+
+    fn write(&mut self, addr: u16, data: u8);
+
+    fn peek(&self, addr: u16) -> u8;
+
+    fn tick(&mut self, _cycles: u8) {}
+}
+
+/// Flat 64 KiB of RAM with nothing mapped into it.
+pub struct Ram {
+    memory: [u8; 0x10000],
+}
+
+impl Ram {
+    pub fn new() -> Self {
+        Self {
+            memory: [0; 0x10000],
+        }
+    }
+}
+
+impl Default for Ram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus for Ram {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.peek(addr)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.memory[addr as usize] = data;
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.memory[addr as usize]
+    }
+}
+
+/// A single bus cycle, as a logic analyser on the address and data lines
+/// would see it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusAccess {
+    Read(u16, u8),
+    Write(u16, u8),
+}
+
+/// Passes everything through to another bus and records each read and
+/// write in order. `peek` is not a bus cycle and is not recorded.
+pub struct RecordingBus<B: Bus = Ram> {
+    pub inner: B,
+    pub accesses: Vec<BusAccess>,
+}
+
+impl<B: Bus> RecordingBus<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            accesses: Vec::new(),
+        }
+    }
+}
+
+impl<B: Bus> Bus for RecordingBus<B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        let data = self.inner.read(addr);
+        self.accesses.push(BusAccess::Read(addr, data));
+        data
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.inner.write(addr, data);
+        self.accesses.push(BusAccess::Write(addr, data));
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.inner.peek(addr)
+    }
+
+    fn tick(&mut self, cycles: u8) {
+        self.inner.tick(cycles);
+    }
+}
+
+const RAM: u16 = 0x0000;
+const RAM_MIRRORS_END: u16 = 0x1FFF;
+const PPU_REGISTERS: u16 = 0x2000;
+const PPU_REGISTERS_MIRRORS_END: u16 = 0x3FFF;
+const APU_IO_REGISTERS: u16 = 0x4000;
+const APU_IO_REGISTERS_END: u16 = 0x401F;
+const PRG_ROM: u16 = 0x8000;
+
+/// The NES CPU memory map.
+///
+/// The 2 KiB of internal RAM is mirrored up to 0x1FFF and the eight PPU
+/// registers are mirrored up to 0x3FFF. PPU and APU registers are plain
+/// latches until those chips are emulated. PRG ROM is mapped at 0x8000,
+/// with a single 16 KiB bank mirrored into 0xC000 like NROM-128.
+pub struct NesBus {
+    cpu_vram: [u8; 0x0800],
+    ppu_registers: [u8; 8],
+    apu_io_registers: [u8; 0x20],
+    prg_rom: Vec<u8>,
+}
+
+impl NesBus {
+    pub fn new(prg_rom: Vec<u8>) -> Self {
+        Self {
+            cpu_vram: [0; 0x0800],
+            ppu_registers: [0; 8],
+            apu_io_registers: [0; 0x20],
+            prg_rom,
+        }
+    }
+
+    fn read_prg_rom(&self, addr: u16) -> u8 {
+        if self.prg_rom.is_empty() {
+            return 0;
+        }
+        let offset = (addr - PRG_ROM) as usize % self.prg_rom.len();
+        self.prg_rom[offset]
+    }
+}
+
+impl Bus for NesBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.peek(addr)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            RAM..=RAM_MIRRORS_END => self.cpu_vram[(addr & 0x07FF) as usize] = data,
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => {
+                self.ppu_registers[(addr & 0x0007) as usize] = data
+            }
+            APU_IO_REGISTERS..=APU_IO_REGISTERS_END => {
+                self.apu_io_registers[(addr - APU_IO_REGISTERS) as usize] = data
+            }
+            _ => {}
+        }
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        match addr {
+            RAM..=RAM_MIRRORS_END => self.cpu_vram[(addr & 0x07FF) as usize],
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => {
+                self.ppu_registers[(addr & 0x0007) as usize]
+            }
+            APU_IO_REGISTERS..=APU_IO_REGISTERS_END => {
+                self.apu_io_registers[(addr - APU_IO_REGISTERS) as usize]
+            }
+            PRG_ROM..=0xFFFF => self.read_prg_rom(addr),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_ram_reads_back_writes() {
+        let mut ram = Ram::new();
+        ram.write(0xBEEF, 0x42);
+
+        assert_eq!(ram.read(0xBEEF), 0x42);
+        assert_eq!(ram.peek(0xBEEF), 0x42);
+    }
+
+    #[test]
+    fn test_recording_bus_records_reads_and_writes() {
+        let mut bus = RecordingBus::new(Ram::new());
+        bus.write(0x0010, 0x42);
+        bus.read(0x0010);
+        bus.peek(0x0010);
+
+        assert_eq!(
+            bus.accesses,
+            vec![
+                BusAccess::Write(0x0010, 0x42),
+                BusAccess::Read(0x0010, 0x42)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nes_ram_is_mirrored() {
+        let mut bus = NesBus::new(vec![]);
+        bus.write(0x0801, 0x42);
+
+        assert_eq!(bus.read(0x0001), 0x42);
+        assert_eq!(bus.read(0x1001), 0x42);
+        assert_eq!(bus.read(0x1801), 0x42);
+    }
+
+    #[test]
+    fn test_nes_ppu_registers_are_mirrored() {
+        let mut bus = NesBus::new(vec![]);
+        bus.write(0x3FFE, 0x42);
+
+        assert_eq!(bus.read(0x2006), 0x42);
+        assert_eq!(bus.read(0x200E), 0x42);
+    }
+
+    #[test]
+    fn test_nes_apu_io_registers() {
+        let mut bus = NesBus::new(vec![]);
+        bus.write(0x4015, 0x0F);
+
+        assert_eq!(bus.read(0x4015), 0x0F);
+        assert_eq!(bus.read(0x4014), 0x00);
+    }
+
+    #[test]
+    fn test_nes_16k_prg_rom_is_mirrored() {
+        let mut prg_rom = vec![0; 0x4000];
+        prg_rom[0x3FFC] = 0x00;
+        prg_rom[0x3FFD] = 0xC0;
+        let mut bus = NesBus::new(prg_rom);
+
+        assert_eq!(bus.read(0xBFFD), 0xC0);
+        assert_eq!(bus.read(0xFFFD), 0xC0);
+    }
+
+    #[test]
+    fn test_nes_prg_rom_ignores_writes() {
+        let mut bus = NesBus::new(vec![0x11; 0x8000]);
+        bus.write(0x8000, 0x42);
+
+        assert_eq!(bus.read(0x8000), 0x11);
+    }
+}
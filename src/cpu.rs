@@ -1,189 +1,4538 @@
 #![allow(dead_code, clippy::upper_case_acronyms)]
 
-pub struct CPU {
+use std::fmt;
+
+use crate::bus::{Bus, Ram};
+use crate::opcode::{AddressingMode, Class, Mnemonic, Opcode};
+use crate::status::Status;
+use crate::trace::{trace, Tracer};
+
+const STACK: u16 = 0x0100;
+const STACK_RESET: u8 = 0xFD;
+const STATUS_RESET: Status = Status::UNUSED.union(Status::INTERRUPT_DISABLE);
+
+const PROGRAM_START: u16 = 0x8000;
+const NMI_VECTOR: u16 = 0xFFFA;
+const RESET_VECTOR: u16 = 0xFFFC;
+const IRQ_VECTOR: u16 = 0xFFFE;
+const RESET_CYCLES: u8 = 7;
+const INTERRUPT_CYCLES: u8 = 7;
+
+pub struct CPU<B: Bus = Ram> {
     pub register_a: u8,
     pub register_x: u8,
-    pub status: u8,
+    pub register_y: u8,
+    pub status: Status,
+    pub stack_pointer: u8,
     pub program_counter: u16,
+    /// Total CPU cycles elapsed, including the reset sequence.
+    pub cycles: u64,
+    pub bus: B,
+    /// Which chip is emulated. Defaults to the NES's 2A03.
+    pub variant: Variant,
+    /// What XAA, LXA, LAS, SHA, SHX, SHY and TAS do.
+    pub unstable_opcodes: UnstableOpcodes,
+    /// Called with a nestest.log line for every instruction, just before
+    /// it runs. See `trace::trace`.
+    pub tracer: Option<Tracer>,
+    page_crossed: bool,
+    extra_cycles: u8,
+    nmi_line: bool,
+    nmi_pending: bool,
+    irq_line: bool,
+    polled_interrupt: Option<Interrupt>,
+    /// Where a KIL opcode locked the CPU up, and which one, if any.
+    jammed: Option<(u16, u8)>,
+    /// The instruction or interrupt `tick` is partway through.
+    in_flight: Option<MicroState>,
 }
 
-impl CPU {
+impl CPU<Ram> {
     pub fn new() -> Self {
+        Self::with_bus(Ram::new())
+    }
+}
+
+impl Default for CPU<Ram> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: Bus> CPU<B> {
+    pub fn with_bus(bus: B) -> Self {
         Self {
             register_a: 0,
             register_x: 0,
-            status: 0,
+            register_y: 0,
+            status: STATUS_RESET,
+            stack_pointer: STACK_RESET,
             program_counter: 0,
+            cycles: 0,
+            bus,
+            variant: Variant::default(),
+            unstable_opcodes: UnstableOpcodes::default(),
+            tracer: None,
+            page_crossed: false,
+            extra_cycles: 0,
+            nmi_line: false,
+            nmi_pending: false,
+            irq_line: false,
+            polled_interrupt: None,
+            jammed: None,
+            in_flight: None,
+        }
+    }
+
+    /// Drives the edge-triggered NMI input. An NMI is latched when the line
+    /// goes from inactive to active and stays pending until serviced.
+    pub fn set_nmi_line(&mut self, active: bool) {
+        if active && !self.nmi_line {
+            self.nmi_pending = true;
         }
+        self.nmi_line = active;
     }
 
-    pub fn interpret(&mut self, program: &[u8]) {
-        self.program_counter = 0;
+    /// Drives the level-triggered IRQ input. An IRQ is taken after every
+    /// instruction for as long as the line is held active and I is clear.
+    pub fn set_irq_line(&mut self, active: bool) {
+        self.irq_line = active;
+    }
 
-        loop {
-            let opcode = self.next_opcode(program);
+    /// Whether a KIL opcode has halted the CPU. Only `reset` recovers.
+    pub fn is_jammed(&self) -> bool {
+        self.jammed.is_some()
+    }
+
+    /// The PC and opcode of the KIL that halted the CPU, until `reset`.
+    pub fn jammed(&self) -> Option<(u16, u8)> {
+        self.jammed
+    }
+
+    pub fn mem_read(&mut self, addr: u16) -> u8 {
+        self.bus.read(addr)
+    }
+
+    pub fn mem_write(&mut self, addr: u16, data: u8) {
+        self.bus.write(addr, data);
+    }
+
+    pub fn mem_read_u16(&mut self, addr: u16) -> u16 {
+        let lo = self.mem_read(addr) as u16;
+        let hi = self.mem_read(addr.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+    pub fn mem_write_u16(&mut self, addr: u16, data: u16) {
+        self.mem_write(addr, data as u8);
+        self.mem_write(addr.wrapping_add(1), (data >> 8) as u8);
+    }
+
+    /// Writes `program` through the bus at `address` and points the reset
+    /// vector at it, so the next `reset` starts executing there.
+    pub fn load(&mut self, program: &[u8], address: u16) -> Result<(), CpuError> {
+        if address as usize + program.len() > 0x10000 {
+            return Err(CpuError::OutOfBounds {
+                address,
+                len: program.len(),
+            });
+        }
+        for (offset, byte) in program.iter().enumerate() {
+            self.mem_write(address + offset as u16, *byte);
+        }
+        self.mem_write_u16(RESET_VECTOR, address);
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.register_a = 0;
+        self.register_x = 0;
+        self.register_y = 0;
+        self.status = STATUS_RESET;
+        self.stack_pointer = STACK_RESET;
+        self.program_counter = self.mem_read_u16(RESET_VECTOR);
+        self.nmi_pending = false;
+        self.polled_interrupt = None;
+        self.jammed = None;
+        self.in_flight = None;
+        self.cycles = RESET_CYCLES as u64;
+        self.bus.tick(RESET_CYCLES);
+    }
 
-            match opcode {
-                Opcode::LDA(param) => {
-                    self.set_register(Register::A, param);
+    pub fn load_and_run(&mut self, program: &[u8]) -> Result<(), CpuError> {
+        self.load(program, PROGRAM_START)?;
+        self.reset();
+        self.interpret()
+    }
+
+    /// Runs instructions until the next one is a BRK, leaving it unexecuted.
+    pub fn interpret(&mut self) -> Result<(), CpuError> {
+        self.run_until(|cpu| cpu.bus.peek(cpu.program_counter) == 0x00)
+    }
+
+    /// Runs whole instructions until at least `cycles` cycles have elapsed
+    /// and returns how many actually did, which may overshoot by part of an
+    /// instruction.
+    pub fn run_for_cycles(&mut self, cycles: u64) -> Result<u64, CpuError> {
+        let mut elapsed = 0;
+        while elapsed < cycles {
+            elapsed += self.step()?.cycles as u64;
+        }
+        Ok(elapsed)
+    }
+
+    /// Runs instructions until `predicate` holds, checking it before each one.
+    pub fn run_until<F>(&mut self, mut predicate: F) -> Result<(), CpuError>
+    where
+        F: FnMut(&Self) -> bool,
+    {
+        while !predicate(self) {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Executes exactly one instruction. A jammed CPU executes nothing and
+    /// ignores interrupts; every step reports the same `Jammed` error. An
+    /// instruction that `tick` has started is run to its end instead.
+    pub fn step(&mut self) -> Result<StepResult, CpuError> {
+        if self.in_flight.is_some() {
+            loop {
+                if let Some(result) = self.tick()? {
+                    return Ok(result);
                 }
-                Opcode::TAX => self.set_register(Register::X, self.register_a),
-                Opcode::INX => self.inc_register(Register::X),
-                Opcode::BRK => {
-                    break;
+            }
+        }
+
+        if let Some((pc, opcode)) = self.jammed {
+            return Err(CpuError::Jammed { pc, opcode });
+        }
+
+        if let Some(interrupt) = self.polled_interrupt.take() {
+            // The fetched opcode is discarded and PC is not incremented,
+            // then the sequence is the same as BRK's.
+            self.dummy_read(self.program_counter);
+            self.dummy_read(self.program_counter);
+            self.interrupt(interrupt);
+            self.cycles += INTERRUPT_CYCLES as u64;
+            self.bus.tick(INTERRUPT_CYCLES);
+            return Ok(StepResult {
+                cycles: INTERRUPT_CYCLES,
+                opcode: 0x00,
+                interrupt_serviced: true,
+            });
+        }
+
+        self.emit_trace();
+        let pc = self.program_counter;
+        let code = self.fetch();
+        let opcode = Opcode::decode(code, self.variant);
+        let interrupt_disable = self.status.contains(Status::INTERRUPT_DISABLE);
+        self.extra_cycles = 0;
+
+        if opcode.mnemonic.is_unstable() && self.unstable_opcodes == UnstableOpcodes::Reject {
+            return Err(CpuError::UnknownOpcode { opcode: code, pc });
+        }
+
+        // Instructions without an operand still read the byte after the
+        // opcode, except for the 65C02's single-cycle NOPs.
+        let mode = opcode.mode;
+        if matches!(mode, AddressingMode::Implied | AddressingMode::Accumulator)
+            && opcode.cycles > 1
+        {
+            self.dummy_read(self.program_counter);
+        }
+
+        match opcode.class {
+            Class::Implied => self.execute_implied(opcode.mnemonic),
+            Class::Read => {
+                let value = self.operand_value(mode);
+                self.execute_read(opcode, value);
+            }
+            Class::Write => {
+                let addr = self.operand_address(mode, Access::Write);
+                self.store(opcode.mnemonic, addr);
+            }
+            Class::ReadModifyWrite => self.read_modify_write(opcode),
+            Class::Control => match opcode.mnemonic {
+                Mnemonic::JMP => {
+                    self.program_counter = self.operand_address(mode, Access::Read);
+                }
+                Mnemonic::JSR => {
+                    // The high byte of the target is only fetched after the
+                    // return address, which points at it, has been pushed.
+                    let lo = self.fetch() as u16;
+                    self.dummy_read(STACK + self.stack_pointer as u16);
+                    self.stack_push_u16(self.program_counter);
+                    let hi = self.fetch() as u16;
+                    self.program_counter = (hi << 8) | lo;
+                }
+                Mnemonic::RTS => {
+                    self.dummy_read(STACK + self.stack_pointer as u16);
+                    let return_address = self.stack_pop_u16();
+                    self.dummy_read(return_address);
+                    self.program_counter = return_address.wrapping_add(1);
+                }
+                Mnemonic::RTI => {
+                    self.dummy_read(STACK + self.stack_pointer as u16);
+                    self.pull_status();
+                    self.program_counter = self.stack_pop_u16();
+                }
+                Mnemonic::PHA | Mnemonic::PHP | Mnemonic::PHX | Mnemonic::PHY => {
+                    self.stack_push(self.pushed_value(opcode.mnemonic));
+                }
+                Mnemonic::PLA | Mnemonic::PLP | Mnemonic::PLX | Mnemonic::PLY => {
+                    self.dummy_read(STACK + self.stack_pointer as u16);
+                    self.pull_into(opcode.mnemonic);
+                }
+                Mnemonic::BRK => {
+                    // BRK skips a padding byte, so RTI returns two bytes after it.
+                    self.program_counter = self.program_counter.wrapping_add(1);
+                    self.interrupt(Interrupt::Brk);
+                }
+                Mnemonic::JAM => {
+                    // The CPU locks up refetching the same byte until reset.
+                    self.program_counter = pc;
+                    self.jammed = Some((pc, code));
+                    return Err(CpuError::Jammed { pc, opcode: code });
                 }
-                Opcode::Unknown(value) => unimplemented!("Opcode: 0x{:X}", value),
+                branch => self.branch(self.branch_taken(branch), mode),
+            },
+        }
+
+        self.finish_instruction(opcode, interrupt_disable);
+
+        let cycles = opcode.cycles + self.extra_cycles;
+        self.cycles += cycles as u64;
+        self.bus.tick(cycles);
+
+        Ok(StepResult {
+            cycles,
+            opcode: code,
+            interrupt_serviced: false,
+        })
+    }
+
+    fn emit_trace(&mut self) {
+        if self.tracer.is_none() {
+            return;
+        }
+        let line = trace(self);
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer(&line);
+        }
+    }
+
+    /// Polls for interrupts at the end of an instruction and adds the
+    /// cycles it took beyond the base count in its opcode.
+    fn finish_instruction(&mut self, opcode: Opcode, interrupt_disable: bool) {
+        // CLI, SEI and PLP only change I on their last cycle, after the
+        // interrupt poll, so their effect on IRQs is one instruction late.
+        match opcode.mnemonic {
+            Mnemonic::CLI | Mnemonic::SEI | Mnemonic::PLP => {
+                self.poll_interrupts(interrupt_disable)
             }
+            _ => self.poll_interrupts(self.status.contains(Status::INTERRUPT_DISABLE)),
+        }
+
+        if opcode.page_cross_penalty && self.page_crossed {
+            self.extra_cycles += 1;
+        }
+        // The 65C02 spends a cycle correcting the flags after BCD.
+        if self.variant == Variant::Cmos65C02
+            && matches!(opcode.mnemonic, Mnemonic::ADC | Mnemonic::SBC)
+            && self.status.contains(Status::DECIMAL)
+        {
+            self.extra_cycles += 1;
         }
     }
 
-    fn next_opcode(&mut self, program: &[u8]) -> Opcode {
-        let opcode = program[self.program_counter as usize];
-        self.program_counter += 1;
-        match opcode {
-            0x00 => Opcode::BRK,
-            0xA9 => {
-                let opcode = Opcode::LDA(program[self.program_counter as usize]);
-                self.program_counter += 1;
-                opcode
+    /// Runs a single cycle and returns the result of the instruction or
+    /// interrupt sequence it finished, if any. Each cycle makes at most one
+    /// bus access and is followed by `Bus::tick(1)`, so other chips can run
+    /// in between, and an NMI or IRQ raised partway through an instruction
+    /// is polled at its end rather than after the next one. Otherwise a run
+    /// of ticks has the same effect as the matching `step`. Errors are
+    /// reported on the cycle that fetched the opcode, which is not counted.
+    pub fn tick(&mut self) -> Result<Option<StepResult>, CpuError> {
+        let state = match self.in_flight.take() {
+            Some(mut state) => {
+                state.cycle += 1;
+                if state.total.is_none() && self.micro_op(&mut state) {
+                    self.complete(&mut state);
+                }
+                state
+            }
+            None => self.first_cycle()?,
+        };
+
+        self.cycles += 1;
+        self.bus.tick(1);
+
+        match state.total {
+            Some(total) if state.cycle >= total => Ok(Some(StepResult {
+                cycles: state.cycle,
+                opcode: state.opcode.code,
+                interrupt_serviced: state.interrupt.is_some(),
+            })),
+            _ => {
+                self.in_flight = Some(state);
+                Ok(None)
             }
-            0xAA => Opcode::TAX,
-            0xE8 => Opcode::INX,
-            value => Opcode::Unknown(value),
         }
     }
 
-    fn set_register(&mut self, register: Register, param: u8) {
-        match register {
-            Register::A => {
-                self.register_a = param;
+    /// Fetches and decodes the next opcode, or discards it when an
+    /// interrupt was polled at the end of the previous instruction.
+    fn first_cycle(&mut self) -> Result<MicroState, CpuError> {
+        if let Some((pc, opcode)) = self.jammed {
+            return Err(CpuError::Jammed { pc, opcode });
+        }
 
-                if self.register_a == 0 {
-                    self.set_flag(Flag::Zero, true);
-                } else {
-                    self.set_flag(Flag::Zero, false);
-                }
-                if self.register_a & 0b1000_0000 != 0 {
-                    self.set_flag(Flag::Negative, true);
-                } else {
-                    self.set_flag(Flag::Negative, false);
-                }
+        if let Some(interrupt) = self.polled_interrupt.take() {
+            self.dummy_read(self.program_counter);
+            let opcode = Opcode::decode(0x00, self.variant);
+            return Ok(MicroState::new(opcode, Some(interrupt), false));
+        }
+
+        self.emit_trace();
+        let pc = self.program_counter;
+        let code = self.fetch();
+        let opcode = Opcode::decode(code, self.variant);
+        let interrupt_disable = self.status.contains(Status::INTERRUPT_DISABLE);
+        self.extra_cycles = 0;
+        self.page_crossed = false;
+
+        if opcode.mnemonic.is_unstable() && self.unstable_opcodes == UnstableOpcodes::Reject {
+            return Err(CpuError::UnknownOpcode { opcode: code, pc });
+        }
+        if opcode.mnemonic == Mnemonic::JAM {
+            self.program_counter = pc;
+            self.jammed = Some((pc, code));
+            return Err(CpuError::Jammed { pc, opcode: code });
+        }
+
+        let mut state = MicroState::new(opcode, None, interrupt_disable);
+        if opcode.mode == AddressingMode::Immediate {
+            state.addr = self.program_counter;
+            state.address_ready = Some(1);
+            self.program_counter = self.program_counter.wrapping_add(1);
+        }
+        if opcode.cycles == 1 {
+            // The 65C02's single-cycle NOPs are done once fetched.
+            self.complete(&mut state);
+        }
+        Ok(state)
+    }
+
+    /// Records how long the sequence lasts once its last bus access is
+    /// made. The 65C02 sometimes takes longer than its accesses, to fix up
+    /// decimal flags for example, and `tick` idles for the difference.
+    fn complete(&mut self, state: &mut MicroState) {
+        let total = match state.interrupt {
+            Some(_) => INTERRUPT_CYCLES,
+            None => {
+                self.finish_instruction(state.opcode, state.interrupt_disable);
+                state.opcode.cycles + self.extra_cycles
             }
-            Register::X => {
-                self.register_x = param;
-                if self.register_x == 0 {
-                    self.set_flag(Flag::Zero, true);
-                } else {
-                    self.set_flag(Flag::Zero, false);
-                }
-                if self.register_x & 0b1000_0000 != 0 {
-                    self.set_flag(Flag::Negative, true);
-                } else {
-                    self.set_flag(Flag::Negative, false);
-                }
+        };
+        debug_assert!(state.cycle <= total, "{:?} overran", state.opcode);
+        state.total = Some(total);
+    }
+
+    /// Runs cycle `state.cycle` of the current sequence and returns whether
+    /// it made the last bus access.
+    fn micro_op(&mut self, state: &mut MicroState) -> bool {
+        if let Some(interrupt) = state.interrupt {
+            return self.interrupt_cycle(state, interrupt);
+        }
+
+        let opcode = state.opcode;
+        match opcode.class {
+            Class::Implied => {
+                self.dummy_read(self.program_counter);
+                self.execute_implied(opcode.mnemonic);
+                true
+            }
+            Class::ReadModifyWrite if opcode.mode == AddressingMode::Accumulator => {
+                self.dummy_read(self.program_counter);
+                self.register_a = self.modify(opcode.mnemonic, self.register_a);
+                true
             }
+            Class::Control => self.control_cycle(state),
+            class => match state.address_ready {
+                Some(ready) => self.operand_cycle(state, state.cycle - ready - 1),
+                None => {
+                    let access = match class {
+                        Class::Read => Access::Read,
+                        Class::Write => Access::Write,
+                        _ => self.modify_access(opcode),
+                    };
+                    if self.address_cycle(state, access) {
+                        state.address_ready = Some(state.cycle);
+                    }
+                    false
+                }
+            },
         }
     }
 
-    fn inc_register(&mut self, register: Register) {
-        match register {
-            Register::A => self.set_register(Register::A, self.register_a + 1),
-            Register::X => self.set_register(Register::X, self.register_a + 1),
+    /// One cycle of resolving the operand address into `state.addr`, making
+    /// the same accesses as `operand_address`. Returns whether it is ready.
+    fn address_cycle(&mut self, state: &mut MicroState, access: Access) -> bool {
+        use AddressingMode::*;
+
+        match (state.opcode.mode, state.cycle - 2) {
+            (ZeroPage, 0) => {
+                state.addr = self.fetch() as u16;
+                true
+            }
+            (ZeroPageX | ZeroPageY | IndirectX | IndirectY | ZeroPageIndirect, 0) => {
+                state.pointer = self.fetch();
+                false
+            }
+            (ZeroPageX, 1) => {
+                self.dummy_read(state.pointer as u16);
+                state.addr = state.pointer.wrapping_add(self.register_x) as u16;
+                true
+            }
+            (ZeroPageY, 1) => {
+                self.dummy_read(state.pointer as u16);
+                state.addr = state.pointer.wrapping_add(self.register_y) as u16;
+                true
+            }
+            (Absolute | AbsoluteX | AbsoluteY, 0) => {
+                state.base = self.fetch() as u16;
+                false
+            }
+            (Absolute, 1) => {
+                state.addr = state.base | ((self.fetch() as u16) << 8);
+                true
+            }
+            (AbsoluteX, 1) => {
+                state.base |= (self.fetch() as u16) << 8;
+                self.start_index(state, self.register_x, access)
+            }
+            (AbsoluteY, 1) => {
+                state.base |= (self.fetch() as u16) << 8;
+                self.start_index(state, self.register_y, access)
+            }
+            (IndirectX, 1) => {
+                self.dummy_read(state.pointer as u16);
+                state.pointer = state.pointer.wrapping_add(self.register_x);
+                false
+            }
+            (IndirectX, 2) | (IndirectY | ZeroPageIndirect, 1) => {
+                state.base = self.mem_read(state.pointer as u16) as u16;
+                false
+            }
+            (IndirectX, 3) | (ZeroPageIndirect, 2) => {
+                let hi = self.mem_read(state.pointer.wrapping_add(1) as u16) as u16;
+                state.addr = state.base | (hi << 8);
+                true
+            }
+            (IndirectY, 2) => {
+                let hi = self.mem_read(state.pointer.wrapping_add(1) as u16) as u16;
+                state.base |= hi << 8;
+                self.start_index(state, self.register_y, access)
+            }
+            (AbsoluteX | AbsoluteY, 2) | (IndirectY, 3) => {
+                self.read_unfixed(state.base, state.addr);
+                true
+            }
+            (mode, step) => unreachable!("{:?} has no address cycle {}", mode, step),
         }
     }
 
-    fn get_flag(&self, flag: Flag) -> bool {
-        match flag {
-            Flag::Zero => self.status & 0b0000_0010 != 0b00,
-            Flag::Negative => self.status & 0b1000_0000 != 0,
+    /// Adds the index to `state.base` and returns whether the address can
+    /// be used without first reading from it unfixed, as `index` decides.
+    fn start_index(&mut self, state: &mut MicroState, index: u8, access: Access) -> bool {
+        state.addr = state.base.wrapping_add(index as u16);
+        self.page_crossed = state.base & 0xFF00 != state.addr & 0xFF00;
+        !self.page_crossed && access == Access::Read
+    }
+
+    /// The cycles after the operand address is known: a read, a store, or
+    /// the read, modify and write of a read-modify-write instruction.
+    fn operand_cycle(&mut self, state: &mut MicroState, step: u8) -> bool {
+        match (state.opcode.class, step) {
+            (Class::Read, 0) => {
+                let value = self.mem_read(state.addr);
+                self.execute_read(state.opcode, value);
+                true
+            }
+            (Class::Write, 0) => {
+                self.store(state.opcode.mnemonic, state.addr);
+                true
+            }
+            (Class::ReadModifyWrite, 0) => {
+                state.value = self.mem_read(state.addr);
+                false
+            }
+            (Class::ReadModifyWrite, 1) => {
+                self.modify_cycle(state.addr, state.value);
+                false
+            }
+            (Class::ReadModifyWrite, 2) => {
+                let result = self.modify(state.opcode.mnemonic, state.value);
+                self.mem_write(state.addr, result);
+                true
+            }
+            (class, step) => unreachable!("{:?} has no operand cycle {}", class, step),
         }
     }
 
-    fn set_flag(&mut self, flag: Flag, bool: bool) {
-        match flag {
-            Flag::Negative => {
-                if bool {
-                    self.status |= 0b1000_0000;
-                } else {
-                    self.status &= 0b0111_1111;
+    /// One cycle of an instruction with a sequence of its own: branches,
+    /// jumps, subroutine and interrupt returns, pushes, pulls and BRK.
+    fn control_cycle(&mut self, state: &mut MicroState) -> bool {
+        let opcode = state.opcode;
+        let branch = opcode.mode == AddressingMode::Relative;
+        match (opcode.mnemonic, state.cycle) {
+            (Mnemonic::BRK, _) => self.interrupt_cycle(state, Interrupt::Brk),
+            (_, 2) if branch => {
+                let offset = self.fetch() as i8;
+                state.addr = self.program_counter.wrapping_add(offset as u16);
+                !self.branch_taken(opcode.mnemonic)
+            }
+            (_, 3) if branch => {
+                self.dummy_read(self.program_counter);
+                self.extra_cycles += 1;
+                let same_page = self.program_counter & 0xFF00 == state.addr & 0xFF00;
+                if same_page {
+                    self.program_counter = state.addr;
+                }
+                same_page
+            }
+            (_, 4) if branch => {
+                self.dummy_read((self.program_counter & 0xFF00) | (state.addr & 0x00FF));
+                self.extra_cycles += 1;
+                self.program_counter = state.addr;
+                true
+            }
+            (Mnemonic::JMP | Mnemonic::JSR, 2) => {
+                state.base = self.fetch() as u16;
+                false
+            }
+            (Mnemonic::JMP, 3) if opcode.mode == AddressingMode::Absolute => {
+                self.program_counter = state.base | ((self.fetch() as u16) << 8);
+                true
+            }
+            (Mnemonic::JMP, 3) => {
+                state.base |= (self.fetch() as u16) << 8;
+                if opcode.mode == AddressingMode::AbsoluteIndirectX {
+                    state.base = state.base.wrapping_add(self.register_x as u16);
                 }
+                false
+            }
+            (Mnemonic::JMP, cycle) => self.jump_indirect_cycle(state, cycle),
+            (Mnemonic::JSR, 3) => {
+                self.dummy_read(STACK + self.stack_pointer as u16);
+                false
+            }
+            (Mnemonic::JSR, 4) => {
+                self.stack_push((self.program_counter >> 8) as u8);
+                false
+            }
+            (Mnemonic::JSR, 5) => {
+                self.stack_push(self.program_counter as u8);
+                false
+            }
+            (Mnemonic::JSR, 6) => {
+                self.program_counter = state.base | ((self.fetch() as u16) << 8);
+                true
+            }
+            (_, 2) => {
+                self.dummy_read(self.program_counter);
+                false
             }
-            Flag::Zero => {
-                if bool {
-                    self.status |= 0b0000_0010;
+            (Mnemonic::PHA | Mnemonic::PHP | Mnemonic::PHX | Mnemonic::PHY, 3) => {
+                self.stack_push(self.pushed_value(opcode.mnemonic));
+                true
+            }
+            (_, 3) => {
+                self.dummy_read(STACK + self.stack_pointer as u16);
+                false
+            }
+            (Mnemonic::PLA | Mnemonic::PLP | Mnemonic::PLX | Mnemonic::PLY, 4) => {
+                self.pull_into(opcode.mnemonic);
+                true
+            }
+            (Mnemonic::RTI, 4) => {
+                self.pull_status();
+                false
+            }
+            (Mnemonic::RTS, 4) | (Mnemonic::RTI, 5) => {
+                state.base = self.stack_pop() as u16;
+                false
+            }
+            (Mnemonic::RTS, 5) => {
+                state.base |= (self.stack_pop() as u16) << 8;
+                false
+            }
+            (Mnemonic::RTS, 6) => {
+                self.dummy_read(state.base);
+                self.program_counter = state.base.wrapping_add(1);
+                true
+            }
+            (Mnemonic::RTI, 6) => {
+                self.program_counter = state.base | ((self.stack_pop() as u16) << 8);
+                true
+            }
+            (opcode, cycle) => unreachable!("{:?} has no cycle {}", opcode, cycle),
+        }
+    }
+
+    /// Cycles 4 onwards of JMP (abs) and JMP (abs,X), which read the target
+    /// from the pointer in `state.base`. The 65C02 rereads the last operand
+    /// byte first and, unlike the NMOS parts, carries into the high byte.
+    fn jump_indirect_cycle(&mut self, state: &mut MicroState, cycle: u8) -> bool {
+        let cmos = self.variant == Variant::Cmos65C02;
+        match cycle - cmos as u8 {
+            3 => {
+                self.dummy_read(self.program_counter.wrapping_sub(1));
+                false
+            }
+            4 => {
+                state.addr = self.mem_read(state.base) as u16;
+                false
+            }
+            _ => {
+                let next = state.base.wrapping_add(1);
+                let hi = if cmos {
+                    next
                 } else {
-                    self.status &= 0b1111_1101;
+                    (state.base & 0xFF00) | (next & 0x00FF)
+                };
+                self.program_counter = state.addr | ((self.mem_read(hi) as u16) << 8);
+                true
+            }
+        }
+    }
+
+    /// Cycles 2 to 7 of BRK and of hardware interrupts, making the same
+    /// accesses as `interrupt`. The vector is picked on cycle 6, so an NMI
+    /// latched any time before that hijacks the sequence.
+    fn interrupt_cycle(&mut self, state: &mut MicroState, interrupt: Interrupt) -> bool {
+        match state.cycle {
+            2 => {
+                self.dummy_read(self.program_counter);
+                if interrupt == Interrupt::Brk {
+                    self.program_counter = self.program_counter.wrapping_add(1);
                 }
             }
+            3 => self.stack_push((self.program_counter >> 8) as u8),
+            4 => self.stack_push(self.program_counter as u8),
+            5 => self.push_interrupt_status(interrupt),
+            6 => {
+                state.addr = self.interrupt_vector(interrupt);
+                state.base = self.mem_read(state.addr) as u16;
+            }
+            _ => {
+                let hi = self.mem_read(state.addr.wrapping_add(1)) as u16;
+                self.program_counter = (hi << 8) | state.base;
+                return true;
+            }
         }
+        false
     }
-}
 
-enum Flag {
-    Negative,
-    Zero,
-}
+    fn fetch(&mut self) -> u8 {
+        let value = self.mem_read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
+        value
+    }
 
-enum Opcode {
-    BRK,     // 0x00
-    LDA(u8), // 0xA9
-    TAX,     // 0xAA
-    INX,
-    Unknown(u8),
-}
+    fn fetch_u16(&mut self) -> u16 {
+        let value = self.mem_read_u16(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(2);
+        value
+    }
 
-enum Register {
-    A,
-    X,
-}
+    /// Resolves the effective address of the operand and records in
+    /// `page_crossed` whether indexing carried into the high byte, making
+    /// the same dummy reads as the hardware along the way.
+    fn operand_address(&mut self, mode: AddressingMode, access: Access) -> u16 {
+        self.page_crossed = false;
+        match mode {
+            AddressingMode::Immediate => {
+                let addr = self.program_counter;
+                self.program_counter = self.program_counter.wrapping_add(1);
+                addr
+            }
+            AddressingMode::ZeroPage => self.fetch() as u16,
+            AddressingMode::ZeroPageX => {
+                let base = self.fetch();
+                self.dummy_read(base as u16);
+                base.wrapping_add(self.register_x) as u16
+            }
+            AddressingMode::ZeroPageY => {
+                let base = self.fetch();
+                self.dummy_read(base as u16);
+                base.wrapping_add(self.register_y) as u16
+            }
+            AddressingMode::Absolute => self.fetch_u16(),
+            AddressingMode::AbsoluteX => {
+                let base = self.fetch_u16();
+                self.index(base, self.register_x, access)
+            }
+            AddressingMode::AbsoluteY => {
+                let base = self.fetch_u16();
+                self.index(base, self.register_y, access)
+            }
+            AddressingMode::Indirect if self.variant == Variant::Cmos65C02 => {
+                let pointer = self.fetch_u16();
+                self.dummy_read(self.program_counter.wrapping_sub(1));
+                self.mem_read_u16(pointer)
+            }
+            AddressingMode::Indirect => {
+                // The 6502 never carries into the high byte of the pointer, so
+                // JMP ($10FF) reads its target from 0x10FF and 0x1000.
+                let pointer = self.fetch_u16();
+                let lo = self.mem_read(pointer) as u16;
+                let hi = self.mem_read((pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF));
+                ((hi as u16) << 8) | lo
+            }
+            AddressingMode::AbsoluteIndirectX => {
+                let pointer = self.fetch_u16().wrapping_add(self.register_x as u16);
+                self.dummy_read(self.program_counter.wrapping_sub(1));
+                self.mem_read_u16(pointer)
+            }
+            AddressingMode::ZeroPageIndirect => {
+                let pointer = self.fetch();
+                self.zero_page_u16(pointer)
+            }
+            AddressingMode::IndirectX => {
+                let base = self.fetch();
+                self.dummy_read(base as u16);
+                self.zero_page_u16(base.wrapping_add(self.register_x))
+            }
+            AddressingMode::IndirectY => {
+                let pointer = self.fetch();
+                let base = self.zero_page_u16(pointer);
+                self.index(base, self.register_y, access)
+            }
+            AddressingMode::Relative => {
+                let offset = self.fetch() as i8;
+                self.program_counter.wrapping_add(offset as u16)
+            }
+            AddressingMode::Accumulator | AddressingMode::Implied => {
+                unreachable!("{:?} has no operand address", mode)
+            }
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use pretty_assertions::assert_eq;
+    /// Adds the index to the low byte first and reads from the result
+    /// before fixing up the high byte. A read that did not cross a page
+    /// has the right value already, so only then is that read skipped here.
+    fn index(&mut self, base: u16, index: u8, access: Access) -> u16 {
+        let addr = base.wrapping_add(index as u16);
+        self.page_crossed = base & 0xFF00 != addr & 0xFF00;
+        if self.page_crossed || access == Access::Write {
+            self.read_unfixed(base, addr);
+        }
+        addr
+    }
 
-    #[test]
-    fn test_0xa9_lda_immediate_load_data() {
-        let mut cpu = CPU::new();
-        cpu.interpret(&[0xa9, 0x05, 0x00]);
-        assert_eq!(cpu.register_a, 0x05);
-        assert_eq!(cpu.get_flag(Flag::Zero), false);
-        assert_eq!(cpu.get_flag(Flag::Negative), false);
+    /// The read made while the high byte of the indexed `addr` is fixed up,
+    /// from `addr` with the high byte of `base`. The 65C02 rereads the last
+    /// operand byte instead.
+    fn read_unfixed(&mut self, base: u16, addr: u16) {
+        if self.variant == Variant::Cmos65C02 {
+            self.dummy_read(self.program_counter.wrapping_sub(1));
+        } else {
+            self.dummy_read((base & 0xFF00) | (addr & 0x00FF));
+        }
     }
 
-    #[test]
-    fn test_0xa9_lda_zero_flag() {
-        let mut cpu = CPU::new();
-        cpu.interpret(&[0xa9, 0x00, 0x00]);
-        assert_eq!(cpu.get_flag(Flag::Zero), true);
-        assert_eq!(cpu.get_flag(Flag::Negative), false);
+    fn operand_value(&mut self, mode: AddressingMode) -> u8 {
+        let addr = self.operand_address(mode, Access::Read);
+        self.mem_read(addr)
     }
 
-    #[test]
-    fn test_0xaa_tax_move_a_to_x() {
-        let mut cpu = CPU::new();
-        cpu.register_a = 10;
-        cpu.interpret(&[0xAA, 0x00]);
+    /// A bus read made only for its side effects, such as acknowledging a
+    /// PPU or mapper register, whose value the CPU throws away.
+    fn dummy_read(&mut self, addr: u16) {
+        self.mem_read(addr);
+    }
 
-        assert_eq!(cpu.register_x, 0xA);
-        assert_eq!(cpu.get_flag(Flag::Zero), false);
-        assert_eq!(cpu.get_flag(Flag::Negative), false);
+    /// Reads a pointer stored in the zero page, wrapping from 0xFF to 0x00.
+    fn zero_page_u16(&mut self, pointer: u8) -> u16 {
+        let lo = self.mem_read(pointer as u16) as u16;
+        let hi = self.mem_read(pointer.wrapping_add(1) as u16) as u16;
+        (hi << 8) | lo
     }
 
-    #[test]
-    fn test_5_ops_working_together() {
-        let mut cpu = CPU::new();
-        cpu.interpret(&[0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
+    /// The 6502 decides whether to take an interrupt before the last cycle
+    /// of each instruction. Lines that change between two `step` calls are
+    /// therefore only acted on after the following instruction.
+    fn poll_interrupts(&mut self, interrupt_disable: bool) {
+        self.polled_interrupt = if self.nmi_pending {
+            Some(Interrupt::Nmi)
+        } else if self.irq_line && !interrupt_disable {
+            Some(Interrupt::Irq)
+        } else {
+            None
+        };
+    }
 
-        assert_eq!(cpu.register_x, 0xc1)
+    /// Pushes PC and P, masks IRQs and jumps through the interrupt's vector.
+    /// Only BRK pushes P with the break bit set.
+    ///
+    /// An NMI that is latched before the vector is fetched hijacks a BRK or
+    /// IRQ in progress: the pushed P is unchanged but the NMI vector is used
+    /// and that NMI is consumed.
+    fn interrupt(&mut self, interrupt: Interrupt) {
+        self.stack_push_u16(self.program_counter);
+        self.push_interrupt_status(interrupt);
+        let vector = self.interrupt_vector(interrupt);
+        self.program_counter = self.mem_read_u16(vector);
     }
 
-    #[test]
-    fn test_inx_overflow() {
-        let mut cpu = CPU::new();
-        cpu.register_x = 0xff;
-        cpu.interpret(&[0xe8, 0xe8, 0x00]);
+    fn push_interrupt_status(&mut self, interrupt: Interrupt) {
+        let mut status = self.status | Status::UNUSED;
+        status.set(Status::BREAK, interrupt == Interrupt::Brk);
+        self.stack_push(status.bits());
+        self.status.insert(Status::INTERRUPT_DISABLE);
+        if self.variant == Variant::Cmos65C02 {
+            self.status.remove(Status::DECIMAL);
+        }
+    }
 
-        assert_eq!(cpu.register_x, 1)
+    fn interrupt_vector(&mut self, interrupt: Interrupt) -> u16 {
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            NMI_VECTOR
+        } else {
+            interrupt.vector()
+        }
+    }
+
+    fn stack_push(&mut self, data: u8) {
+        self.mem_write(STACK + self.stack_pointer as u16, data);
+        self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+    }
+
+    fn stack_pop(&mut self) -> u8 {
+        self.stack_pointer = self.stack_pointer.wrapping_add(1);
+        self.mem_read(STACK + self.stack_pointer as u16)
+    }
+
+    fn stack_push_u16(&mut self, data: u16) {
+        self.stack_push((data >> 8) as u8);
+        self.stack_push(data as u8);
+    }
+
+    fn pushed_value(&self, mnemonic: Mnemonic) -> u8 {
+        match mnemonic {
+            Mnemonic::PHA => self.register_a,
+            Mnemonic::PHP => (self.status | Status::BREAK | Status::UNUSED).bits(),
+            Mnemonic::PHX => self.register_x,
+            Mnemonic::PHY => self.register_y,
+            _ => unreachable!("{:?} does not push", mnemonic),
+        }
+    }
+
+    /// Pops the value PLA, PLP, PLX or PLY pulls. Each of them spends a
+    /// cycle reading the stack before moving SP, which is left to the caller.
+    fn pull_into(&mut self, mnemonic: Mnemonic) {
+        let register = match mnemonic {
+            Mnemonic::PLA => Register::A,
+            Mnemonic::PLX => Register::X,
+            Mnemonic::PLY => Register::Y,
+            Mnemonic::PLP => return self.pull_status(),
+            _ => unreachable!("{:?} does not pull", mnemonic),
+        };
+        let value = self.stack_pop();
+        self.set_register(register, value);
+    }
+
+    fn stack_pop_u16(&mut self) -> u16 {
+        let lo = self.stack_pop() as u16;
+        let hi = self.stack_pop() as u16;
+        (hi << 8) | lo
+    }
+
+    /// PLP and RTI ignore the break bit on the stack; there is no latch for
+    /// it, just as there is none for the always-set unused bit.
+    fn pull_status(&mut self) {
+        let status = Status::from_bits_retain(self.stack_pop());
+        self.status = (status - Status::BREAK) | Status::UNUSED;
+    }
+
+    fn decimal_mode(&self) -> bool {
+        self.status.contains(Status::DECIMAL) && self.variant.has_decimal_mode()
+    }
+
+    fn add_with_carry(&mut self, value: u8) {
+        if self.decimal_mode() {
+            self.add_decimal(value);
+        } else {
+            self.add_to_register_a(value);
+        }
+    }
+
+    fn subtract_with_carry(&mut self, value: u8) {
+        if self.decimal_mode() {
+            self.subtract_decimal(value);
+        } else {
+            self.add_to_register_a(!value);
+        }
+    }
+
+    /// BCD addition. Each nibble that goes past 9 is corrected by adding 6
+    /// and carries into the next one. Only C is meaningful on the NMOS part:
+    /// Z comes from the binary sum, and N and V from the sum before the high
+    /// nibble is corrected. The 65C02 sets N and Z from the result.
+    fn add_decimal(&mut self, value: u8) {
+        let carry = self.status.contains(Status::CARRY) as u16;
+        let a = self.register_a as u16;
+        let value = value as u16;
+        let binary = (a + value + carry) as u8;
+
+        let mut lo = (a & 0x0F) + (value & 0x0F) + carry;
+        if lo > 0x09 {
+            lo = ((lo + 0x06) & 0x0F) + 0x10;
+        }
+        let mut sum = (a & 0xF0) + (value & 0xF0) + lo;
+        self.status.set(
+            Status::OVERFLOW,
+            (a ^ sum) & (value ^ sum) & 0b1000_0000 != 0,
+        );
+        self.status.set(Status::NEGATIVE, sum & 0b1000_0000 != 0);
+        self.status.set(Status::ZERO, binary == 0);
+        if sum > 0x9F {
+            sum += 0x60;
+        }
+
+        self.status.set(Status::CARRY, sum > 0xFF);
+        self.register_a = sum as u8;
+        if self.variant == Variant::Cmos65C02 {
+            self.update_zero_and_negative_flags(self.register_a);
+        }
+    }
+
+    /// Corrects the result of ARR in decimal mode the way the adder would
+    /// correct a BCD sum, nibble by nibble, judging each by the nibble of
+    /// the AND before rotating. N, Z and V are left as they were before the
+    /// correction, and C is set by the high nibble's.
+    fn arr_decimal_fixup(&mut self, and: u8, result: u8) {
+        let (hi, lo) = (and >> 4, and & 0x0F);
+        let mut result = result;
+        if lo + (lo & 1) > 5 {
+            result = (result & 0xF0) | (result.wrapping_add(6) & 0x0F);
+        }
+        let carry = hi + (hi & 1) > 5;
+        if carry {
+            result = result.wrapping_add(0x60);
+        }
+        self.status.set(Status::CARRY, carry);
+        self.register_a = result;
+    }
+
+    /// BCD subtraction. Every flag on the NMOS part, and C and V on the
+    /// 65C02, are those of the binary subtraction. The two differ in how
+    /// they correct invalid BCD digits.
+    fn subtract_decimal(&mut self, value: u8) {
+        let borrow = !self.status.contains(Status::CARRY) as i16;
+        let a = self.register_a as i16;
+        let operand = value as i16;
+
+        let lo = (a & 0x0F) - (operand & 0x0F) - borrow;
+        let difference = if self.variant == Variant::Cmos65C02 {
+            let mut difference = a - operand - borrow;
+            if difference < 0 {
+                difference -= 0x60;
+            }
+            if lo < 0 {
+                difference -= 0x06;
+            }
+            difference
+        } else {
+            let mut lo = lo;
+            if lo < 0 {
+                lo = ((lo - 0x06) & 0x0F) - 0x10;
+            }
+            let mut difference = (a & 0xF0) - (operand & 0xF0) + lo;
+            if difference < 0 {
+                difference -= 0x60;
+            }
+            difference
+        };
+
+        self.add_to_register_a(!value);
+        self.register_a = difference as u8;
+        if self.variant == Variant::Cmos65C02 {
+            self.update_zero_and_negative_flags(self.register_a);
+        }
+    }
+
+    fn add_to_register_a(&mut self, value: u8) {
+        let carry = self.status.contains(Status::CARRY) as u16;
+        let sum = self.register_a as u16 + value as u16 + carry;
+        let result = sum as u8;
+
+        self.status.set(Status::CARRY, sum > 0xFF);
+        self.status.set(
+            Status::OVERFLOW,
+            (value ^ result) & (self.register_a ^ result) & 0b1000_0000 != 0,
+        );
+        self.set_register(Register::A, result);
+    }
+
+    fn compare(&mut self, register: Register, value: u8) {
+        let register = self.register(register);
+        self.status.set(Status::CARRY, register >= value);
+        self.update_zero_and_negative_flags(register.wrapping_sub(value));
+    }
+
+    /// Carries out an instruction that only works on registers and flags.
+    fn execute_implied(&mut self, mnemonic: Mnemonic) {
+        match mnemonic {
+            Mnemonic::CLC => self.status.set(Status::CARRY, false),
+            Mnemonic::SEC => self.status.set(Status::CARRY, true),
+            Mnemonic::CLI => self.status.set(Status::INTERRUPT_DISABLE, false),
+            Mnemonic::SEI => self.status.set(Status::INTERRUPT_DISABLE, true),
+            Mnemonic::CLD => self.status.set(Status::DECIMAL, false),
+            Mnemonic::SED => self.status.set(Status::DECIMAL, true),
+            Mnemonic::CLV => self.status.set(Status::OVERFLOW, false),
+            Mnemonic::TAX => self.transfer(Register::A, Register::X),
+            Mnemonic::TAY => self.transfer(Register::A, Register::Y),
+            Mnemonic::TXA => self.transfer(Register::X, Register::A),
+            Mnemonic::TYA => self.transfer(Register::Y, Register::A),
+            Mnemonic::TSX => self.transfer(Register::SP, Register::X),
+            Mnemonic::TXS => self.transfer(Register::X, Register::SP),
+            Mnemonic::INX => self.inc_register(Register::X),
+            Mnemonic::INY => self.inc_register(Register::Y),
+            Mnemonic::DEX => self.dec_register(Register::X),
+            Mnemonic::DEY => self.dec_register(Register::Y),
+            Mnemonic::NOP => {}
+            _ => unreachable!("{:?} is not an implied instruction", mnemonic),
+        }
+    }
+
+    /// Carries out an instruction that reads `value` from its operand.
+    fn execute_read(&mut self, opcode: Opcode, value: u8) {
+        match opcode.mnemonic {
+            Mnemonic::ADC => self.add_with_carry(value),
+            Mnemonic::SBC => self.subtract_with_carry(value),
+            Mnemonic::AND => self.set_register(Register::A, self.register_a & value),
+            Mnemonic::ORA => self.set_register(Register::A, self.register_a | value),
+            Mnemonic::EOR => self.set_register(Register::A, self.register_a ^ value),
+            Mnemonic::BIT if opcode.mode == AddressingMode::Immediate => {
+                // The 65C02's BIT #imm has no memory operand to take N and V from.
+                self.status.set(Status::ZERO, self.register_a & value == 0);
+            }
+            Mnemonic::BIT => {
+                self.status.set(Status::ZERO, self.register_a & value == 0);
+                self.status.set(Status::NEGATIVE, value & 0b1000_0000 != 0);
+                self.status.set(Status::OVERFLOW, value & 0b0100_0000 != 0);
+            }
+            Mnemonic::CMP => self.compare(Register::A, value),
+            Mnemonic::CPX => self.compare(Register::X, value),
+            Mnemonic::CPY => self.compare(Register::Y, value),
+            Mnemonic::LDA => self.set_register(Register::A, value),
+            Mnemonic::LDX => self.set_register(Register::X, value),
+            Mnemonic::LDY => self.set_register(Register::Y, value),
+            Mnemonic::NOP => {}
+            Mnemonic::LAX => {
+                self.set_register(Register::A, value);
+                self.set_register(Register::X, value);
+            }
+            Mnemonic::ANC => {
+                let value = self.register_a & value;
+                self.set_register(Register::A, value);
+                self.status.set(Status::CARRY, value & 0b1000_0000 != 0);
+            }
+            Mnemonic::ALR => {
+                let value = self.register_a & value;
+                let result = self.shift_right(value);
+                self.set_register(Register::A, result);
+            }
+            Mnemonic::ARR => {
+                // C and V come from bits 6 and 5 of the rotated result,
+                // as if the AND had gone through the adder.
+                let value = self.register_a & value;
+                let carry = self.status.contains(Status::CARRY) as u8;
+                let result = (value >> 1) | (carry << 7);
+                self.set_register(Register::A, result);
+                self.status.set(
+                    Status::OVERFLOW,
+                    ((result >> 6) ^ (result >> 5)) & 0b0000_0001 != 0,
+                );
+                if self.decimal_mode() {
+                    self.arr_decimal_fixup(value, result);
+                } else {
+                    self.status.set(Status::CARRY, result & 0b0100_0000 != 0);
+                }
+            }
+            Mnemonic::AXS => {
+                let and = self.register_a & self.register_x;
+                self.status.set(Status::CARRY, and >= value);
+                self.set_register(Register::X, and.wrapping_sub(value));
+            }
+            Mnemonic::XAA => {
+                let result = (self.register_a | self.unstable_magic()) & self.register_x & value;
+                self.set_register(Register::A, result);
+            }
+            Mnemonic::LXA => {
+                let result = (self.register_a | self.unstable_magic()) & value;
+                self.set_register(Register::A, result);
+                self.set_register(Register::X, result);
+            }
+            Mnemonic::LAS => {
+                let value = value & self.stack_pointer;
+                self.stack_pointer = value;
+                self.set_register(Register::A, value);
+                self.set_register(Register::X, value);
+            }
+            _ => unreachable!("{:?} does not read an operand", opcode),
+        }
+    }
+
+    /// Carries out a store to the operand address `addr`.
+    fn store(&mut self, mnemonic: Mnemonic, addr: u16) {
+        match mnemonic {
+            Mnemonic::STA => self.mem_write(addr, self.register_a),
+            Mnemonic::STX => self.mem_write(addr, self.register_x),
+            Mnemonic::STY => self.mem_write(addr, self.register_y),
+            Mnemonic::STZ => self.mem_write(addr, 0),
+            Mnemonic::SAX => self.mem_write(addr, self.register_a & self.register_x),
+            Mnemonic::SHA => self.store_and_high_byte(addr, self.register_a & self.register_x),
+            Mnemonic::SHX => self.store_and_high_byte(addr, self.register_x),
+            Mnemonic::SHY => self.store_and_high_byte(addr, self.register_y),
+            Mnemonic::TAS => {
+                self.stack_pointer = self.register_a & self.register_x;
+                self.store_and_high_byte(addr, self.stack_pointer);
+            }
+            _ => unreachable!("{:?} is not a store", mnemonic),
+        }
+    }
+
+    /// Runs a read-modify-write instruction on A or on memory.
+    fn read_modify_write(&mut self, opcode: Opcode) {
+        match opcode.mode {
+            AddressingMode::Accumulator => {
+                self.register_a = self.modify(opcode.mnemonic, self.register_a)
+            }
+            mode => {
+                let addr = self.operand_address(mode, self.modify_access(opcode));
+                let value = self.mem_read(addr);
+                self.modify_cycle(addr, value);
+                let result = self.modify(opcode.mnemonic, value);
+                self.mem_write(addr, result);
+            }
+        }
+    }
+
+    /// Returns what a read-modify-write instruction writes back, setting N
+    /// and Z from it. The unofficial combined opcodes then feed it into
+    /// their second operation. TRB and TSB only set Z, from A AND `value`.
+    fn modify(&mut self, mnemonic: Mnemonic, value: u8) -> u8 {
+        let result = match mnemonic {
+            Mnemonic::ASL | Mnemonic::SLO => self.shift_left(value),
+            Mnemonic::LSR | Mnemonic::SRE => self.shift_right(value),
+            Mnemonic::ROL | Mnemonic::RLA => self.rotate_left(value),
+            Mnemonic::ROR | Mnemonic::RRA => self.rotate_right(value),
+            Mnemonic::INC | Mnemonic::ISB => value.wrapping_add(1),
+            Mnemonic::DEC | Mnemonic::DCP => value.wrapping_sub(1),
+            Mnemonic::TRB => {
+                self.status.set(Status::ZERO, self.register_a & value == 0);
+                return value & !self.register_a;
+            }
+            Mnemonic::TSB => {
+                self.status.set(Status::ZERO, self.register_a & value == 0);
+                return value | self.register_a;
+            }
+            _ => unreachable!("{:?} is not a read-modify-write instruction", mnemonic),
+        };
+        self.update_zero_and_negative_flags(result);
+
+        match mnemonic {
+            Mnemonic::SLO => self.set_register(Register::A, self.register_a | result),
+            Mnemonic::RLA => self.set_register(Register::A, self.register_a & result),
+            Mnemonic::SRE => self.set_register(Register::A, self.register_a ^ result),
+            Mnemonic::RRA => self.add_with_carry(result),
+            Mnemonic::DCP => self.compare(Register::A, result),
+            Mnemonic::ISB => self.subtract_with_carry(result),
+            _ => {}
+        }
+        result
+    }
+
+    /// A read-modify-write opcode that only pays for a page cross, like the
+    /// 65C02's shifts, fixes the high byte of its address before reading
+    /// from it as a read does.
+    fn modify_access(&self, opcode: Opcode) -> Access {
+        if opcode.page_cross_penalty {
+            Access::Read
+        } else {
+            Access::Write
+        }
+    }
+
+    /// While a read-modify-write instruction computes its result, the NMOS
+    /// parts write the unmodified value back and the 65C02 reads it again.
+    fn modify_cycle(&mut self, addr: u16, value: u8) {
+        if self.variant == Variant::Cmos65C02 {
+            self.dummy_read(addr);
+        } else {
+            self.mem_write(addr, value);
+        }
+    }
+
+    fn shift_left(&mut self, value: u8) -> u8 {
+        self.status.set(Status::CARRY, value & 0b1000_0000 != 0);
+        value << 1
+    }
+
+    fn shift_right(&mut self, value: u8) -> u8 {
+        self.status.set(Status::CARRY, value & 0b0000_0001 != 0);
+        value >> 1
+    }
+
+    fn rotate_left(&mut self, value: u8) -> u8 {
+        let carry = self.status.contains(Status::CARRY) as u8;
+        self.status.set(Status::CARRY, value & 0b1000_0000 != 0);
+        (value << 1) | carry
+    }
+
+    fn rotate_right(&mut self, value: u8) -> u8 {
+        let carry = self.status.contains(Status::CARRY) as u8;
+        self.status.set(Status::CARRY, value & 0b0000_0001 != 0);
+        (value >> 1) | (carry << 7)
+    }
+
+    /// SHA, SHX, SHY and TAS store `value` ANDed with one more than the high
+    /// byte of the base address. When indexing crosses a page the stored
+    /// value also replaces the high byte of the address written to.
+    fn store_and_high_byte(&mut self, addr: u16, value: u8) {
+        let mut high = (addr >> 8) as u8;
+        if self.page_crossed {
+            high = high.wrapping_sub(1);
+        }
+        let value = value & high.wrapping_add(1);
+        let addr = if self.page_crossed {
+            ((value as u16) << 8) | (addr & 0x00FF)
+        } else {
+            addr
+        };
+        self.mem_write(addr, value);
+    }
+
+    fn unstable_magic(&self) -> u8 {
+        match self.unstable_opcodes {
+            UnstableOpcodes::Emulate { magic } => magic,
+            UnstableOpcodes::Reject => unreachable!("unstable opcodes are rejected before running"),
+        }
+    }
+
+    fn branch_taken(&self, mnemonic: Mnemonic) -> bool {
+        match mnemonic {
+            Mnemonic::BCC => !self.status.contains(Status::CARRY),
+            Mnemonic::BCS => self.status.contains(Status::CARRY),
+            Mnemonic::BNE => !self.status.contains(Status::ZERO),
+            Mnemonic::BEQ => self.status.contains(Status::ZERO),
+            Mnemonic::BPL => !self.status.contains(Status::NEGATIVE),
+            Mnemonic::BMI => self.status.contains(Status::NEGATIVE),
+            Mnemonic::BVC => !self.status.contains(Status::OVERFLOW),
+            Mnemonic::BVS => self.status.contains(Status::OVERFLOW),
+            Mnemonic::BRA => true,
+            _ => unreachable!("{:?} is not a branch", mnemonic),
+        }
+    }
+
+    /// A taken branch costs one extra cycle, and another if the target is
+    /// on a different page from the next instruction.
+    fn branch(&mut self, condition: bool, mode: AddressingMode) {
+        let addr = self.operand_address(mode, Access::Read);
+        if condition {
+            self.dummy_read(self.program_counter);
+            self.extra_cycles += 1;
+            if self.program_counter & 0xFF00 != addr & 0xFF00 {
+                self.dummy_read((self.program_counter & 0xFF00) | (addr & 0x00FF));
+                self.extra_cycles += 1;
+            }
+            self.program_counter = addr;
+        }
+    }
+
+    fn register(&self, register: Register) -> u8 {
+        match register {
+            Register::A => self.register_a,
+            Register::X => self.register_x,
+            Register::Y => self.register_y,
+            Register::SP => self.stack_pointer,
+        }
+    }
+
+    fn register_mut(&mut self, register: Register) -> &mut u8 {
+        match register {
+            Register::A => &mut self.register_a,
+            Register::X => &mut self.register_x,
+            Register::Y => &mut self.register_y,
+            Register::SP => &mut self.stack_pointer,
+        }
+    }
+
+    fn set_register(&mut self, register: Register, param: u8) {
+        *self.register_mut(register) = param;
+        self.update_zero_and_negative_flags(param);
+    }
+
+    /// Copies one register into another. TXS is the only transfer that
+    /// leaves the flags alone.
+    fn transfer(&mut self, from: Register, to: Register) {
+        let value = self.register(from);
+        match to {
+            Register::SP => self.stack_pointer = value,
+            to => self.set_register(to, value),
+        }
+    }
+
+    fn inc_register(&mut self, register: Register) {
+        self.set_register(register, self.register(register).wrapping_add(1));
+    }
+
+    fn dec_register(&mut self, register: Register) {
+        self.set_register(register, self.register(register).wrapping_sub(1));
+    }
+
+    fn update_zero_and_negative_flags(&mut self, value: u8) {
+        self.status.set(Status::ZERO, value == 0);
+        self.status.set(Status::NEGATIVE, value & 0b1000_0000 != 0);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepResult {
+    pub cycles: u8,
+    pub opcode: u8,
+    pub interrupt_serviced: bool,
+}
+
+/// The 6502 family members this core can emulate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    /// The original NMOS 6502, with decimal mode and all its bugs.
+    Nmos6502,
+    /// The NES CPU: an NMOS 6502 with decimal mode disconnected. The D flag
+    /// can still be set and pushed but ADC and SBC ignore it.
+    #[default]
+    Ricoh2A03,
+    /// The CMOS 65C02. It adds BRA, PHX/PHY/PLX/PLY, STZ, TRB/TSB and the
+    /// (zp) and (abs,X) addressing modes, fixes the JMP ($xxFF) bug, clears D
+    /// on interrupts and runs its undefined opcodes as NOPs.
+    Cmos65C02,
+}
+
+impl Variant {
+    fn has_decimal_mode(self) -> bool {
+        self != Variant::Ricoh2A03
+    }
+}
+
+/// How the unstable unofficial opcodes behave. Their results depend on the
+/// chip and even its temperature, so by default they are refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnstableOpcodes {
+    /// Report them as `CpuError::UnknownOpcode`.
+    #[default]
+    Reject,
+    /// Run them as most NMOS parts do. XAA and LXA OR `magic` into A before
+    /// the AND; 0xEE and 0xFF are the common values.
+    Emulate { magic: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    /// The byte at `pc` does not decode to an instruction this core runs.
+    UnknownOpcode { opcode: u8, pc: u16 },
+    /// The KIL/JAM `opcode` at `pc` halted the CPU until the next reset.
+    Jammed { pc: u16, opcode: u8 },
+    /// `len` bytes loaded at `address` would run past 0xFFFF.
+    OutOfBounds { address: u16, len: usize },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::UnknownOpcode { opcode, pc } => {
+                write!(f, "unknown opcode 0x{:02X} at 0x{:04X}", opcode, pc)
+            }
+            CpuError::Jammed { pc, opcode } => {
+                write!(f, "CPU jammed by opcode 0x{:02X} at 0x{:04X}", opcode, pc)
+            }
+            CpuError::OutOfBounds { address, len } => write!(
+                f,
+                "{} bytes at 0x{:04X} do not fit in the address space",
+                len, address
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Interrupt {
+    Nmi,
+    Irq,
+    Brk,
+}
+
+impl Interrupt {
+    fn vector(self) -> u16 {
+        match self {
+            Interrupt::Nmi => NMI_VECTOR,
+            Interrupt::Irq | Interrupt::Brk => IRQ_VECTOR,
+        }
+    }
+}
+
+/// Where `tick` is in the instruction or interrupt it is running.
+#[derive(Debug, Clone, Copy)]
+struct MicroState {
+    /// Cycles run so far, counting the opcode fetch as the first.
+    cycle: u8,
+    opcode: Opcode,
+    /// The hardware interrupt being serviced in place of `opcode`.
+    interrupt: Option<Interrupt>,
+    /// I as it was before the instruction, for the CLI/SEI/PLP poll.
+    interrupt_disable: bool,
+    /// The cycle on which the operand address was resolved.
+    address_ready: Option<u8>,
+    pointer: u8,
+    base: u16,
+    addr: u16,
+    value: u8,
+    /// How many cycles the sequence lasts, known once its last bus access
+    /// is done.
+    total: Option<u8>,
+}
+
+impl MicroState {
+    fn new(opcode: Opcode, interrupt: Option<Interrupt>, interrupt_disable: bool) -> Self {
+        Self {
+            cycle: 1,
+            opcode,
+            interrupt,
+            interrupt_disable,
+            address_ready: None,
+            pointer: 0,
+            base: 0,
+            addr: 0,
+            value: 0,
+            total: None,
+        }
+    }
+}
+
+/// Whether an instruction reads its operand or writes to it. Stores and
+/// read-modify-write instructions cannot act on a guessed address, so
+/// they always spend a cycle reading it before an index carry is fixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Register {
+    A,
+    X,
+    Y,
+    SP,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::bus::{BusAccess, NesBus, RecordingBus};
+    use pretty_assertions::assert_eq;
+
+    fn program(source: &str) -> Vec<u8> {
+        assemble(source).unwrap().bytes
+    }
+
+    #[test]
+    fn test_0xa9_lda_immediate_load_data() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0x05, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0x05);
+        assert_eq!(cpu.status.contains(Status::ZERO), false);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), false);
+    }
+
+    #[test]
+    fn test_0xa9_lda_zero_flag() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0x00, 0x00]).unwrap();
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), false);
+    }
+
+    #[test]
+    fn test_0xaa_tax_move_a_to_x() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0x0a, 0xAA, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_x, 0xA);
+        assert_eq!(cpu.status.contains(Status::ZERO), false);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), false);
+    }
+
+    #[test]
+    fn test_5_ops_working_together() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&program(
+            "
+            lda #$c0
+            tax
+            inx
+            brk
+            ",
+        ))
+        .unwrap();
+
+        assert_eq!(cpu.register_x, 0xc1)
+    }
+
+    #[test]
+    fn test_inx_overflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa2, 0xff, 0xe8, 0xe8, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_x, 1)
+    }
+
+    #[test]
+    fn test_all_official_opcodes_decode() {
+        let official: [u8; 151] = [
+            0x69, 0x65, 0x75, 0x6D, 0x7D, 0x79, 0x61, 0x71, 0x29, 0x25, 0x35, 0x2D, 0x3D, 0x39,
+            0x21, 0x31, 0x0A, 0x06, 0x16, 0x0E, 0x1E, 0x90, 0xB0, 0xF0, 0x24, 0x2C, 0x30, 0xD0,
+            0x10, 0x00, 0x50, 0x70, 0x18, 0xD8, 0x58, 0xB8, 0xC9, 0xC5, 0xD5, 0xCD, 0xDD, 0xD9,
+            0xC1, 0xD1, 0xE0, 0xE4, 0xEC, 0xC0, 0xC4, 0xCC, 0xC6, 0xD6, 0xCE, 0xDE, 0xCA, 0x88,
+            0x49, 0x45, 0x55, 0x4D, 0x5D, 0x59, 0x41, 0x51, 0xE6, 0xF6, 0xEE, 0xFE, 0xE8, 0xC8,
+            0x4C, 0x6C, 0x20, 0xA9, 0xA5, 0xB5, 0xAD, 0xBD, 0xB9, 0xA1, 0xB1, 0xA2, 0xA6, 0xB6,
+            0xAE, 0xBE, 0xA0, 0xA4, 0xB4, 0xAC, 0xBC, 0x4A, 0x46, 0x56, 0x4E, 0x5E, 0xEA, 0x09,
+            0x05, 0x15, 0x0D, 0x1D, 0x19, 0x01, 0x11, 0x48, 0x08, 0x68, 0x28, 0x2A, 0x26, 0x36,
+            0x2E, 0x3E, 0x6A, 0x66, 0x76, 0x6E, 0x7E, 0x40, 0x60, 0xE9, 0xE5, 0xF5, 0xED, 0xFD,
+            0xF9, 0xE1, 0xF1, 0x38, 0xF8, 0x78, 0x85, 0x95, 0x8D, 0x9D, 0x99, 0x81, 0x91, 0x86,
+            0x96, 0x8E, 0x84, 0x94, 0x8C, 0xAA, 0xA8, 0xBA, 0x8A, 0x9A, 0x98,
+        ];
+        for opcode in official {
+            let decoded = Opcode::decode(opcode, Variant::Nmos6502);
+            assert!(
+                decoded.official && !decoded.mnemonic.is_unofficial(),
+                "0x{:02X} should decode to an official opcode",
+                opcode
+            );
+            let cmos = Opcode::decode(opcode, Variant::Cmos65C02);
+            assert_eq!((cmos.mnemonic, cmos.mode), (decoded.mnemonic, decoded.mode));
+        }
+    }
+
+    #[test]
+    fn test_lda_addressing_modes() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0x01);
+        cpu.mem_write(0x0085, 0x02);
+        cpu.mem_write(0x0200, 0x03);
+        cpu.mem_write(0x0205, 0x04);
+        cpu.mem_write(0x0206, 0x05);
+        cpu.mem_write(0x0090, 0x00);
+        cpu.mem_write(0x0091, 0x03);
+        cpu.mem_write(0x0300, 0x06);
+        cpu.mem_write(0x0306, 0x07);
+
+        cpu.load_and_run(&[0xa2, 0x05, 0xa0, 0x06, 0xa5, 0x80, 0x85, 0x40])
+            .unwrap();
+        assert_eq!(cpu.mem_read(0x0040), 0x01);
+
+        let programs: [(&[u8], u8); 7] = [
+            (&[0xa2, 0x05, 0xb5, 0x80, 0x00], 0x02),
+            (&[0xad, 0x00, 0x02, 0x00], 0x03),
+            (&[0xa2, 0x05, 0xbd, 0x00, 0x02, 0x00], 0x04),
+            (&[0xa0, 0x06, 0xb9, 0x00, 0x02, 0x00], 0x05),
+            (&[0xa2, 0x10, 0xa1, 0x80, 0x00], 0x06),
+            (&[0xa0, 0x06, 0xb1, 0x90, 0x00], 0x07),
+            (&[0xa9, 0x80, 0x00], 0x80),
+        ];
+        for (program, expected) in programs {
+            cpu.load_and_run(program).unwrap();
+            assert_eq!(cpu.register_a, expected);
+        }
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_ldx_addressing_modes() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0x01);
+        cpu.mem_write(0x0082, 0x02);
+        cpu.mem_write(0x0200, 0x03);
+        cpu.mem_write(0x0202, 0x04);
+
+        let programs: [(&[u8], u8); 5] = [
+            (&[0xa2, 0x00, 0x00], 0x00),
+            (&[0xa6, 0x80, 0x00], 0x01),
+            (&[0xa0, 0x02, 0xb6, 0x80, 0x00], 0x02),
+            (&[0xae, 0x00, 0x02, 0x00], 0x03),
+            (&[0xa0, 0x02, 0xbe, 0x00, 0x02, 0x00], 0x04),
+        ];
+        for (program, expected) in programs {
+            cpu.load_and_run(program).unwrap();
+            assert_eq!(cpu.register_x, expected);
+        }
+    }
+
+    #[test]
+    fn test_ldy_addressing_modes() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0x01);
+        cpu.mem_write(0x0082, 0x02);
+        cpu.mem_write(0x0200, 0x03);
+        cpu.mem_write(0x0202, 0x04);
+
+        let programs: [(&[u8], u8); 5] = [
+            (&[0xa0, 0xff, 0x00], 0xff),
+            (&[0xa4, 0x80, 0x00], 0x01),
+            (&[0xa2, 0x02, 0xb4, 0x80, 0x00], 0x02),
+            (&[0xac, 0x00, 0x02, 0x00], 0x03),
+            (&[0xa2, 0x02, 0xbc, 0x00, 0x02, 0x00], 0x04),
+        ];
+        for (program, expected) in programs {
+            cpu.load_and_run(program).unwrap();
+            assert_eq!(cpu.register_y, expected);
+        }
+    }
+
+    #[test]
+    fn test_zero_page_x_wraps_around() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x007f, 0x42);
+        cpu.load_and_run(&[0xa2, 0xff, 0xb5, 0x80, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_sta_addressing_modes() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0090, 0x00);
+        cpu.mem_write(0x0091, 0x03);
+        cpu.load_and_run(&[
+            0xa9, 0x11, 0xa2, 0x02, 0xa0, 0x04, // LDA #$11, LDX #$02, LDY #$04
+            0x85, 0x80, // STA $80
+            0x95, 0x80, // STA $80,X
+            0x8d, 0x00, 0x02, // STA $0200
+            0x9d, 0x00, 0x02, // STA $0200,X
+            0x99, 0x00, 0x02, // STA $0200,Y
+            0x81, 0x8e, // STA ($8E,X)
+            0x91, 0x90, // STA ($90),Y
+            0x00,
+        ])
+        .unwrap();
+
+        for addr in [0x0080, 0x0082, 0x0200, 0x0202, 0x0204, 0x0300, 0x0304] {
+            assert_eq!(cpu.mem_read(addr), 0x11, "0x{:04X}", addr);
+        }
+    }
+
+    #[test]
+    fn test_stx_sty_addressing_modes() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[
+            0xa2, 0x22, 0xa0, 0x01, // LDX #$22, LDY #$01
+            0x86, 0x80, // STX $80
+            0x96, 0x80, // STX $80,Y
+            0x8e, 0x00, 0x02, // STX $0200
+            0xa2, 0x03, 0xa0, 0x33, // LDX #$03, LDY #$33
+            0x84, 0x90, // STY $90
+            0x94, 0x90, // STY $90,X
+            0x8c, 0x10, 0x02, // STY $0210
+            0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(cpu.mem_read(0x0080), 0x22);
+        assert_eq!(cpu.mem_read(0x0081), 0x22);
+        assert_eq!(cpu.mem_read(0x0200), 0x22);
+        assert_eq!(cpu.mem_read(0x0090), 0x33);
+        assert_eq!(cpu.mem_read(0x0093), 0x33);
+        assert_eq!(cpu.mem_read(0x0210), 0x33);
+    }
+
+    #[test]
+    fn test_adc_without_carry() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0x10, 0x69, 0x20, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_a, 0x30);
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+        assert_eq!(cpu.status.contains(Status::OVERFLOW), false);
+    }
+
+    #[test]
+    fn test_adc_sets_carry_and_adds_it() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0xff, 0x69, 0x02, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0x01);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+
+        cpu.load_and_run(&[0xa9, 0xff, 0x69, 0x02, 0x69, 0x01, 0x00])
+            .unwrap();
+        assert_eq!(cpu.register_a, 0x03);
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+    }
+
+    #[test]
+    fn test_adc_overflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0x50, 0x69, 0x50, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_a, 0xa0);
+        assert_eq!(cpu.status.contains(Status::OVERFLOW), true);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_adc_memory_modes() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0x01);
+        cpu.mem_write(0x0081, 0x02);
+        cpu.mem_write(0x0200, 0x04);
+        cpu.mem_write(0x0201, 0x08);
+        cpu.mem_write(0x0202, 0x10);
+        cpu.mem_write(0x0090, 0x00);
+        cpu.mem_write(0x0091, 0x03);
+        cpu.mem_write(0x0300, 0x20);
+        cpu.mem_write(0x0302, 0x40);
+        cpu.load_and_run(&[
+            0xa2, 0x01, 0xa0, 0x02, // LDX #$01, LDY #$02
+            0x65, 0x80, // ADC $80
+            0x75, 0x80, // ADC $80,X
+            0x6d, 0x00, 0x02, // ADC $0200
+            0x7d, 0x00, 0x02, // ADC $0200,X
+            0x79, 0x00, 0x02, // ADC $0200,Y
+            0x61, 0x8f, // ADC ($8F,X)
+            0x71, 0x90, // ADC ($90),Y
+            0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(cpu.register_a, 0x7f);
+    }
+
+    #[test]
+    fn test_sbc_with_borrow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0x38, 0xa9, 0x10, 0xe9, 0x01, 0x00])
+            .unwrap();
+        assert_eq!(cpu.register_a, 0x0f);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+
+        cpu.load_and_run(&[0x38, 0xa9, 0x0f, 0xe9, 0x10, 0x00])
+            .unwrap();
+        assert_eq!(cpu.register_a, 0xff);
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+
+        cpu.load_and_run(&[0x18, 0xa9, 0xff, 0xe9, 0x00, 0x00])
+            .unwrap();
+        assert_eq!(cpu.register_a, 0xfe);
+    }
+
+    #[test]
+    fn test_sbc_overflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0x38, 0xa9, 0x80, 0xe9, 0x01, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.register_a, 0x7f);
+        assert_eq!(cpu.status.contains(Status::OVERFLOW), true);
+    }
+
+    #[test]
+    fn test_sbc_memory_modes() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0x01);
+        cpu.mem_write(0x0081, 0x02);
+        cpu.mem_write(0x0200, 0x04);
+        cpu.mem_write(0x0201, 0x08);
+        cpu.mem_write(0x0202, 0x10);
+        cpu.mem_write(0x0090, 0x00);
+        cpu.mem_write(0x0091, 0x03);
+        cpu.mem_write(0x0300, 0x20);
+        cpu.mem_write(0x0302, 0x40);
+        cpu.load_and_run(&[
+            0x38, 0xa9, 0xff, 0xa2, 0x01, 0xa0, 0x02, // SEC, LDA #$FF, LDX #$01, LDY #$02
+            0xe5, 0x80, // SBC $80
+            0xf5, 0x80, // SBC $80,X
+            0xed, 0x00, 0x02, // SBC $0200
+            0xfd, 0x00, 0x02, // SBC $0200,X
+            0xf9, 0x00, 0x02, // SBC $0200,Y
+            0xe1, 0x8f, // SBC ($8F,X)
+            0xf1, 0x90, // SBC ($90),Y
+            0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(cpu.register_a, 0x80);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_and_ora_eor_immediate() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0b1100_1100, 0x29, 0b1010_1010, 0x00])
+            .unwrap();
+        assert_eq!(cpu.register_a, 0b1000_1000);
+
+        cpu.load_and_run(&[0xa9, 0b1000_1000, 0x09, 0b0000_0011, 0x00])
+            .unwrap();
+        assert_eq!(cpu.register_a, 0b1000_1011);
+
+        cpu.load_and_run(&[0xa9, 0b1000_1011, 0x49, 0b1000_1011, 0x00])
+            .unwrap();
+        assert_eq!(cpu.register_a, 0);
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+    }
+
+    #[test]
+    fn test_and_memory_modes() {
+        let mut cpu = CPU::new();
+        for addr in [0x0080, 0x0081, 0x0200, 0x0201, 0x0202, 0x0300, 0x0302] {
+            cpu.mem_write(addr, 0b1111_1110);
+        }
+        cpu.mem_write(0x0090, 0x00);
+        cpu.mem_write(0x0091, 0x03);
+        cpu.mem_write(0x0300, 0b0111_1111);
+        cpu.load_and_run(&[
+            0xa9, 0xff, 0xa2, 0x01, 0xa0, 0x02, // LDA #$FF, LDX #$01, LDY #$02
+            0x25, 0x80, 0x35, 0x80, 0x2d, 0x00, 0x02, 0x3d, 0x00, 0x02, 0x39, 0x00, 0x02, 0x21,
+            0x8f, 0x31, 0x90, 0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(cpu.register_a, 0b0111_1110);
+    }
+
+    #[test]
+    fn test_ora_memory_modes() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0x01);
+        cpu.mem_write(0x0081, 0x02);
+        cpu.mem_write(0x0200, 0x04);
+        cpu.mem_write(0x0201, 0x08);
+        cpu.mem_write(0x0202, 0x10);
+        cpu.mem_write(0x0090, 0x00);
+        cpu.mem_write(0x0091, 0x03);
+        cpu.mem_write(0x0300, 0x20);
+        cpu.mem_write(0x0302, 0x40);
+        cpu.load_and_run(&[
+            0xa2, 0x01, 0xa0, 0x02, // LDX #$01, LDY #$02
+            0x05, 0x80, 0x15, 0x80, 0x0d, 0x00, 0x02, 0x1d, 0x00, 0x02, 0x19, 0x00, 0x02, 0x01,
+            0x8f, 0x11, 0x90, 0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(cpu.register_a, 0x7f);
+    }
+
+    #[test]
+    fn test_eor_memory_modes() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0x01);
+        cpu.mem_write(0x0081, 0x02);
+        cpu.mem_write(0x0200, 0x04);
+        cpu.mem_write(0x0201, 0x08);
+        cpu.mem_write(0x0202, 0x10);
+        cpu.mem_write(0x0090, 0x00);
+        cpu.mem_write(0x0091, 0x03);
+        cpu.mem_write(0x0300, 0x20);
+        cpu.mem_write(0x0302, 0x40);
+        cpu.load_and_run(&[
+            0xa9, 0xff, 0xa2, 0x01, 0xa0, 0x02, // LDA #$FF, LDX #$01, LDY #$02
+            0x45, 0x80, 0x55, 0x80, 0x4d, 0x00, 0x02, 0x5d, 0x00, 0x02, 0x59, 0x00, 0x02, 0x41,
+            0x8f, 0x51, 0x90, 0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(cpu.register_a, 0x80);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_bit() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0b1100_0000);
+        cpu.mem_write(0x0200, 0b0000_0001);
+        cpu.load_and_run(&[0xa9, 0x01, 0x24, 0x80, 0x00]).unwrap();
+
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+        assert_eq!(cpu.status.contains(Status::OVERFLOW), true);
+        assert_eq!(cpu.register_a, 0x01);
+
+        cpu.load_and_run(&[0xa9, 0x01, 0x2c, 0x00, 0x02, 0x00])
+            .unwrap();
+        assert_eq!(cpu.status.contains(Status::ZERO), false);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), false);
+        assert_eq!(cpu.status.contains(Status::OVERFLOW), false);
+    }
+
+    #[test]
+    fn test_cmp() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0x10, 0xc9, 0x10, 0x00]).unwrap();
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+
+        cpu.load_and_run(&[0xa9, 0x10, 0xc9, 0x20, 0x00]).unwrap();
+        assert_eq!(cpu.status.contains(Status::ZERO), false);
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+
+        cpu.load_and_run(&[0xa9, 0x10, 0xc9, 0x01, 0x00]).unwrap();
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), false);
+    }
+
+    #[test]
+    fn test_cmp_memory_modes() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0090, 0x00);
+        cpu.mem_write(0x0091, 0x03);
+        let operands: [&[u8]; 7] = [
+            &[0xc5, 0x80],
+            &[0xd5, 0x80],
+            &[0xcd, 0x00, 0x02],
+            &[0xdd, 0x00, 0x02],
+            &[0xd9, 0x00, 0x02],
+            &[0xc1, 0x8f],
+            &[0xd1, 0x90],
+        ];
+        let targets = [0x0080, 0x0081, 0x0200, 0x0201, 0x0202, 0x0300, 0x0302];
+        for (operand, target) in operands.into_iter().zip(targets) {
+            // LDA #$42, LDX #$01, LDY #$02, CMP ..., BRK
+            let mut program = vec![0xa9, 0x42, 0xa2, 0x01, 0xa0, 0x02];
+            program.extend_from_slice(operand);
+            program.push(0x00);
+
+            cpu.mem_write(target, 0x42);
+            cpu.load_and_run(&program).unwrap();
+            assert_eq!(
+                cpu.status.contains(Status::ZERO),
+                true,
+                "0x{:02X}",
+                operand[0]
+            );
+            cpu.mem_write(target, 0x00);
+        }
+    }
+
+    #[test]
+    fn test_cpx_cpy() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0x05);
+        cpu.mem_write(0x0200, 0x06);
+        cpu.load_and_run(&[0xa2, 0x05, 0xe0, 0x05, 0x00]).unwrap();
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+        cpu.load_and_run(&[0xa2, 0x05, 0xe4, 0x80, 0x00]).unwrap();
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+        cpu.load_and_run(&[0xa2, 0x05, 0xec, 0x00, 0x02, 0x00])
+            .unwrap();
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+
+        cpu.load_and_run(&[0xa0, 0x06, 0xc0, 0x05, 0x00]).unwrap();
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+        assert_eq!(cpu.status.contains(Status::ZERO), false);
+        cpu.load_and_run(&[0xa0, 0x06, 0xc4, 0x80, 0x00]).unwrap();
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+        cpu.load_and_run(&[0xa0, 0x06, 0xcc, 0x00, 0x02, 0x00])
+            .unwrap();
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+    }
+
+    #[test]
+    fn test_asl() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0b1000_0001, 0x0a, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0b0000_0010);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+
+        cpu.mem_write(0x0080, 0x01);
+        cpu.mem_write(0x0081, 0x01);
+        cpu.mem_write(0x0200, 0x01);
+        cpu.mem_write(0x0201, 0x40);
+        cpu.load_and_run(&[
+            0xa2, 0x01, 0x06, 0x80, 0x16, 0x80, 0x0e, 0x00, 0x02, 0x1e, 0x00, 0x02, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(cpu.mem_read(0x0080), 0x02);
+        assert_eq!(cpu.mem_read(0x0081), 0x02);
+        assert_eq!(cpu.mem_read(0x0200), 0x02);
+        assert_eq!(cpu.mem_read(0x0201), 0x80);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+    }
+
+    #[test]
+    fn test_lsr() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0b0000_0011, 0x4a, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0b0000_0001);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+
+        cpu.mem_write(0x0080, 0x02);
+        cpu.mem_write(0x0081, 0x02);
+        cpu.mem_write(0x0200, 0x02);
+        cpu.mem_write(0x0201, 0x01);
+        cpu.load_and_run(&[
+            0xa2, 0x01, 0x46, 0x80, 0x56, 0x80, 0x4e, 0x00, 0x02, 0x5e, 0x00, 0x02, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(cpu.mem_read(0x0080), 0x01);
+        assert_eq!(cpu.mem_read(0x0081), 0x01);
+        assert_eq!(cpu.mem_read(0x0200), 0x01);
+        assert_eq!(cpu.mem_read(0x0201), 0x00);
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_rol() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0x38, 0xa9, 0b1000_0000, 0x2a, 0x00])
+            .unwrap();
+        assert_eq!(cpu.register_a, 0b0000_0001);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+
+        cpu.mem_write(0x0080, 0x01);
+        cpu.mem_write(0x0081, 0x01);
+        cpu.mem_write(0x0200, 0x01);
+        cpu.mem_write(0x0201, 0x01);
+        cpu.load_and_run(&[
+            0x38, 0xa2, 0x01, 0x26, 0x80, 0x36, 0x80, 0x2e, 0x00, 0x02, 0x3e, 0x00, 0x02, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(cpu.mem_read(0x0080), 0x03);
+        assert_eq!(cpu.mem_read(0x0081), 0x02);
+        assert_eq!(cpu.mem_read(0x0200), 0x02);
+        assert_eq!(cpu.mem_read(0x0201), 0x02);
+    }
+
+    #[test]
+    fn test_ror() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0x38, 0xa9, 0b0000_0001, 0x6a, 0x00])
+            .unwrap();
+        assert_eq!(cpu.register_a, 0b1000_0000);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+
+        cpu.mem_write(0x0080, 0x02);
+        cpu.mem_write(0x0081, 0x02);
+        cpu.mem_write(0x0200, 0x02);
+        cpu.mem_write(0x0201, 0x02);
+        cpu.load_and_run(&[
+            0x38, 0xa2, 0x01, 0x66, 0x80, 0x76, 0x80, 0x6e, 0x00, 0x02, 0x7e, 0x00, 0x02, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(cpu.mem_read(0x0080), 0x81);
+        assert_eq!(cpu.mem_read(0x0081), 0x01);
+        assert_eq!(cpu.mem_read(0x0200), 0x01);
+        assert_eq!(cpu.mem_read(0x0201), 0x01);
+    }
+
+    #[test]
+    fn test_inc_dec_memory() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0xff);
+        cpu.mem_write(0x0081, 0x01);
+        cpu.mem_write(0x0200, 0x7f);
+        cpu.mem_write(0x0201, 0x10);
+        cpu.load_and_run(&[
+            0xa2, 0x01, 0xe6, 0x80, 0xf6, 0x80, 0xee, 0x00, 0x02, 0xfe, 0x00, 0x02, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(cpu.mem_read(0x0080), 0x00);
+        assert_eq!(cpu.mem_read(0x0081), 0x02);
+        assert_eq!(cpu.mem_read(0x0200), 0x80);
+        assert_eq!(cpu.mem_read(0x0201), 0x11);
+
+        cpu.load_and_run(&[
+            0xa2, 0x01, 0xc6, 0x80, 0xd6, 0x80, 0xce, 0x00, 0x02, 0xde, 0x00, 0x02, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(cpu.mem_read(0x0080), 0xff);
+        assert_eq!(cpu.mem_read(0x0081), 0x01);
+        assert_eq!(cpu.mem_read(0x0200), 0x7f);
+        assert_eq!(cpu.mem_read(0x0201), 0x10);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), false);
+    }
+
+    #[test]
+    fn test_inc_sets_zero_flag() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0xff);
+        cpu.load_and_run(&[0xe6, 0x80, 0x00]).unwrap();
+
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+    }
+
+    #[test]
+    fn test_iny_dex_dey() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xc8, 0xc8, 0x00]).unwrap();
+        assert_eq!(cpu.register_y, 2);
+
+        cpu.load_and_run(&[0xca, 0x00]).unwrap();
+        assert_eq!(cpu.register_x, 0xff);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+
+        cpu.load_and_run(&[0xc8, 0xc8, 0x88, 0x88, 0x00]).unwrap();
+        assert_eq!(cpu.register_y, 0);
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+    }
+
+    #[test]
+    fn test_transfers() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0x42, 0xa8, 0x00]).unwrap();
+        assert_eq!(cpu.register_y, 0x42);
+
+        cpu.load_and_run(&[0xa0, 0x80, 0x98, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0x80);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+
+        cpu.load_and_run(&[0xa2, 0x00, 0x8a, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0x00);
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+    }
+
+    #[test]
+    fn test_tsx_txs() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa2, 0x80, 0x9a, 0xa2, 0x00, 0xba, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.stack_pointer, 0x80);
+        assert_eq!(cpu.register_x, 0x80);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_txs_does_not_touch_flags() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa2, 0x00, 0xa9, 0x01, 0x9a, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.stack_pointer, 0x00);
+        assert_eq!(cpu.status.contains(Status::ZERO), false);
+    }
+
+    #[test]
+    fn test_flag_instructions() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0x38, 0x78, 0xf8, 0x00]).unwrap();
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+        assert_eq!(cpu.status.contains(Status::INTERRUPT_DISABLE), true);
+        assert_eq!(cpu.status.contains(Status::DECIMAL), true);
+
+        cpu.mem_write(0x0080, 0x40);
+        cpu.load_and_run(&[0x38, 0x78, 0xf8, 0x24, 0x80, 0x18, 0x58, 0xd8, 0xb8, 0x00])
+            .unwrap();
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+        assert_eq!(cpu.status.contains(Status::INTERRUPT_DISABLE), false);
+        assert_eq!(cpu.status.contains(Status::DECIMAL), false);
+        assert_eq!(cpu.status.contains(Status::OVERFLOW), false);
+    }
+
+    #[test]
+    fn test_nop() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xea, 0xea, 0x00]).unwrap();
+
+        assert_eq!(cpu.program_counter, 0x8002);
+        assert_eq!(cpu.status, Status::UNUSED | Status::INTERRUPT_DISABLE);
+    }
+
+    #[test]
+    fn test_branches_taken() {
+        // Each branch skips an LDA #$FF when its condition holds.
+        let programs: [&[u8]; 8] = [
+            &[0x18, 0x90, 0x02, 0xa9, 0xff, 0x00],       // CLC, BCC
+            &[0x38, 0xb0, 0x02, 0xa9, 0xff, 0x00],       // SEC, BCS
+            &[0xa2, 0x01, 0xd0, 0x02, 0xa9, 0xff, 0x00], // LDX #1, BNE
+            &[0xa2, 0x00, 0xf0, 0x02, 0xa9, 0xff, 0x00], // LDX #0, BEQ
+            &[0xa2, 0x01, 0x10, 0x02, 0xa9, 0xff, 0x00], // LDX #1, BPL
+            &[0xa2, 0x80, 0x30, 0x02, 0xa9, 0xff, 0x00], // LDX #$80, BMI
+            &[0xb8, 0x50, 0x02, 0xa9, 0xff, 0x00],       // CLV, BVC
+            &[0xa9, 0x40, 0x69, 0x40, 0x70, 0x02, 0xa9, 0xff, 0x00], // overflow, BVS
+        ];
+        for program in programs {
+            let mut cpu = CPU::new();
+            cpu.load_and_run(program).unwrap();
+            assert_ne!(cpu.register_a, 0xff, "{:02X?}", program);
+        }
+    }
+
+    #[test]
+    fn test_branches_not_taken() {
+        let programs: [&[u8]; 8] = [
+            &[0x38, 0x90, 0x02, 0xa9, 0xff, 0x00],
+            &[0x18, 0xb0, 0x02, 0xa9, 0xff, 0x00],
+            &[0xa2, 0x00, 0xd0, 0x02, 0xa9, 0xff, 0x00],
+            &[0xa2, 0x01, 0xf0, 0x02, 0xa9, 0xff, 0x00],
+            &[0xa2, 0x80, 0x10, 0x02, 0xa9, 0xff, 0x00],
+            &[0xa2, 0x01, 0x30, 0x02, 0xa9, 0xff, 0x00],
+            &[0xa9, 0x40, 0x69, 0x40, 0x50, 0x02, 0xa9, 0xff, 0x00],
+            &[0xb8, 0x70, 0x02, 0xa9, 0xff, 0x00],
+        ];
+        for program in programs {
+            let mut cpu = CPU::new();
+            cpu.load_and_run(program).unwrap();
+            assert_eq!(cpu.register_a, 0xff, "{:02X?}", program);
+        }
+    }
+
+    #[test]
+    fn test_branch_backwards_loop() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&program(
+            "
+                    ldx #$05
+            loop:   iny
+                    dex
+                    bne loop
+                    brk
+            ",
+        ))
+        .unwrap();
+
+        assert_eq!(cpu.register_y, 5);
+        assert_eq!(cpu.register_x, 0);
+    }
+
+    #[test]
+    fn test_jmp_absolute() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&program(
+            "
+                    .org $8000
+                    jmp skip
+                    lda #$ff
+            skip:   lda #$01
+                    brk
+            ",
+        ))
+        .unwrap();
+
+        assert_eq!(cpu.register_a, 0x01);
+    }
+
+    #[test]
+    fn test_jmp_indirect() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0200, 0x08);
+        cpu.mem_write(0x0201, 0x80);
+        cpu.load_and_run(&[
+            0x6c, 0x00, 0x02, 0xa9, 0xff, 0x00, 0x00, 0x00, 0xa9, 0x01, 0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(cpu.register_a, 0x01);
+    }
+
+    #[test]
+    fn test_jmp_indirect_page_boundary_bug() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x02ff, 0x08);
+        cpu.mem_write(0x0200, 0x80);
+        cpu.mem_write(0x0300, 0x40);
+        cpu.load_and_run(&[
+            0x6c, 0xff, 0x02, 0xa9, 0xff, 0x00, 0x00, 0x00, 0xa9, 0x01, 0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(cpu.register_a, 0x01);
+    }
+
+    #[test]
+    fn test_jsr_rts() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&program(
+            "
+                    .org $8000
+                    jsr sub
+                    ldx #$02
+                    brk
+            sub:    lda #$01
+                    rts
+            ",
+        ))
+        .unwrap();
+
+        assert_eq!(cpu.register_a, 0x01);
+        assert_eq!(cpu.register_x, 0x02);
+        assert_eq!(cpu.stack_pointer, 0xfd);
+    }
+
+    #[test]
+    fn test_jsr_pushes_return_address_minus_one() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xea, 0x20, 0x05, 0x80, 0xea, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.stack_pointer, 0xfb);
+        assert_eq!(cpu.mem_read(0x01fd), 0x80);
+        assert_eq!(cpu.mem_read(0x01fc), 0x03);
+    }
+
+    #[test]
+    fn test_pha_pla() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0x80, 0x48, 0xa9, 0x00, 0x68, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.register_a, 0x80);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+        assert_eq!(cpu.stack_pointer, 0xfd);
+    }
+
+    #[test]
+    fn test_php_plp() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0x38, 0xf8, 0x08, 0x18, 0xd8, 0x28, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.mem_read(0x01fd), 0b0011_1101);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+        assert_eq!(cpu.status.contains(Status::DECIMAL), true);
+        assert_eq!(cpu.status.contains(Status::BREAK), false);
+    }
+
+    #[test]
+    fn test_rti() {
+        let mut cpu = CPU::new();
+        // Push return address 0x800C and a status with carry set, then RTI.
+        cpu.load_and_run(&[
+            0xa9, 0x80, 0x48, 0xa9, 0x0c, 0x48, 0xa9, 0x01, 0x48, 0x40, 0xa2, 0xff, 0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(cpu.register_x, 0x00);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+        assert_eq!(cpu.stack_pointer, 0xfd);
+    }
+
+    #[test]
+    fn test_indirect_y_crosses_page() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0080, 0xff);
+        cpu.mem_write(0x0081, 0x02);
+        cpu.mem_write(0x0300, 0x42);
+        cpu.load_and_run(&[0xa0, 0x01, 0xb1, 0x80, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_indirect_x_pointer_wraps_in_zero_page() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x00ff, 0x00);
+        cpu.mem_write(0x0000, 0x03);
+        cpu.mem_write(0x0300, 0x42);
+        cpu.load_and_run(&[0xa2, 0xff, 0xa1, 0x00, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_absolute_x_wraps_address_space() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0001, 0x42);
+        cpu.load_and_run(&[0xa2, 0x02, 0xbd, 0xff, 0xff, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.register_a, 0x42);
+    }
+
+    #[test]
+    fn test_mem_read_u16_is_little_endian() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0200, 0x34);
+        cpu.mem_write(0x0201, 0x12);
+
+        assert_eq!(cpu.mem_read_u16(0x0200), 0x1234);
+    }
+
+    #[test]
+    fn test_mem_write_u16_is_little_endian() {
+        let mut cpu = CPU::new();
+        cpu.mem_write_u16(0x0200, 0x1234);
+
+        assert_eq!(cpu.mem_read(0x0200), 0x34);
+        assert_eq!(cpu.mem_read(0x0201), 0x12);
+    }
+
+    #[test]
+    fn test_load_sets_reset_vector() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xa9, 0x01, 0x00], 0x8000).unwrap();
+
+        assert_eq!(cpu.mem_read_u16(0xfffc), 0x8000);
+        assert_eq!(cpu.mem_read(0x8000), 0xa9);
+    }
+
+    #[test]
+    fn test_reset_starts_at_reset_vector() {
+        let mut cpu = CPU::new();
+        cpu.register_a = 0x42;
+        cpu.load(&[0x00], 0xc000).unwrap();
+        cpu.reset();
+
+        assert_eq!(cpu.program_counter, 0xc000);
+        assert_eq!(cpu.register_a, 0);
+    }
+
+    #[test]
+    fn test_runs_from_nes_prg_rom() {
+        let mut prg_rom = vec![0; 0x4000];
+        // LDA #$42; STA $0801; BRK
+        prg_rom[..6].copy_from_slice(&[0xa9, 0x42, 0x8d, 0x01, 0x08, 0x00]);
+        prg_rom[0x3ffc] = 0x00;
+        prg_rom[0x3ffd] = 0xc0;
+
+        let mut cpu = CPU::with_bus(NesBus::new(prg_rom));
+        cpu.reset();
+        cpu.interpret().unwrap();
+
+        assert_eq!(cpu.program_counter, 0xc005);
+        assert_eq!(cpu.mem_read(0x0001), 0x42);
+    }
+
+    #[test]
+    fn test_reset_initialises_stack_pointer() {
+        let mut cpu = CPU::new();
+        cpu.stack_pointer = 0x00;
+        cpu.load(&[0x00], 0x8000).unwrap();
+        cpu.reset();
+
+        assert_eq!(cpu.stack_pointer, 0xfd);
+    }
+
+    #[test]
+    fn test_stack_push_wraps_within_page_one() {
+        let mut cpu = CPU::new();
+        // LDX #$00; TXS; LDA #$42; PHA; PHA
+        cpu.load_and_run(&[0xa2, 0x00, 0x9a, 0xa9, 0x42, 0x48, 0x48, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.mem_read(0x0100), 0x42);
+        assert_eq!(cpu.mem_read(0x01ff), 0x42);
+        assert_eq!(cpu.mem_read(0x0000), 0x00);
+        assert_eq!(cpu.stack_pointer, 0xfe);
+    }
+
+    #[test]
+    fn test_stack_pop_wraps_within_page_one() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x0100, 0x42);
+        cpu.mem_write(0x0200, 0x24);
+        // LDX #$FF; TXS; PLA
+        cpu.load_and_run(&[0xa2, 0xff, 0x9a, 0x68, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.stack_pointer, 0x00);
+    }
+
+    #[test]
+    fn test_jsr_rts_across_stack_wrap() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&program(
+            "
+                    .org $8000
+                    ldx #$00
+                    txs
+                    jsr sub
+                    ldy #$01
+                    brk
+            sub:    rts
+            ",
+        ))
+        .unwrap();
+
+        assert_eq!(cpu.mem_read(0x0100), 0x80);
+        assert_eq!(cpu.mem_read(0x01ff), 0x05);
+        assert_eq!(cpu.register_y, 0x01);
+        assert_eq!(cpu.stack_pointer, 0x00);
+    }
+
+    #[test]
+    fn test_php_pushes_break_and_unused() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0x08, 0x00]).unwrap();
+
+        assert_eq!(cpu.mem_read(0x01fd), 0b0011_0100);
+        assert_eq!(cpu.status.contains(Status::BREAK), false);
+    }
+
+    #[test]
+    fn test_php_sets_unused_even_when_status_is_empty() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0x08, 0x00], 0x8000).unwrap();
+        cpu.reset();
+        cpu.status = Status::empty();
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.mem_read(0x01fd), 0b0011_0000);
+        assert_eq!(cpu.status, Status::empty());
+    }
+
+    #[test]
+    fn test_plp_ignores_break_and_keeps_unused() {
+        let mut cpu = CPU::new();
+        // LDA #$FF; PHA; PLP
+        cpu.load_and_run(&[0xa9, 0xff, 0x48, 0x28, 0x00]).unwrap();
+
+        assert_eq!(cpu.status, Status::all() - Status::BREAK);
+    }
+
+    #[test]
+    fn test_rti_ignores_break_and_keeps_unused() {
+        let mut cpu = CPU::new();
+        // Push return address 0x800A and status 0x30, then RTI.
+        cpu.load_and_run(&[
+            0xa9, 0x80, 0x48, 0xa9, 0x0a, 0x48, 0xa9, 0x30, 0x48, 0x40, 0x00,
+        ])
+        .unwrap();
+
+        assert_eq!(cpu.status, Status::UNUSED);
+        assert_eq!(cpu.program_counter, 0x800a);
+    }
+
+    #[test]
+    fn test_iny_overflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa0, 0xff, 0xc8, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_y, 0);
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+    }
+
+    #[test]
+    fn test_dex_dey_underflow() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xca, 0x88, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_x, 0xff);
+        assert_eq!(cpu.register_y, 0xff);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_inx_uses_x_not_a() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0xff, 0xa2, 0x10, 0xe8, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.register_x, 0x11);
+        assert_eq!(cpu.register_a, 0xff);
+    }
+
+    #[test]
+    fn test_tay_tya_update_flags() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0x00, 0xa2, 0x01, 0xa8, 0x00])
+            .unwrap();
+        assert_eq!(cpu.register_y, 0x00);
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+
+        cpu.load_and_run(&[0xa0, 0xf0, 0x98, 0x00]).unwrap();
+        assert_eq!(cpu.register_a, 0xf0);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_tsx_reads_stack_pointer_after_reset() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xba, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_x, 0xfd);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_ldy_sty_cpy_share_register_path() {
+        let mut cpu = CPU::new();
+        // LDY #$80; STY $10; CPY $10
+        cpu.load_and_run(&[0xa0, 0x80, 0x84, 0x10, 0xc4, 0x10, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.mem_read(0x0010), 0x80);
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_unknown_opcode_reports_pc() {
+        let mut cpu = CPU::new();
+        let result = cpu.load_and_run(&[0xea, 0x8b, 0x00]);
+
+        assert_eq!(
+            result,
+            Err(CpuError::UnknownOpcode {
+                opcode: 0x8b,
+                pc: 0x8001
+            })
+        );
+    }
+
+    #[test]
+    fn test_jam_opcode_reports_pc() {
+        let mut cpu = CPU::new();
+        let result = cpu.load_and_run(&[0xea, 0xea, 0x02, 0x00]);
+
+        assert_eq!(
+            result,
+            Err(CpuError::Jammed {
+                pc: 0x8002,
+                opcode: 0x02
+            })
+        );
+        assert_eq!(cpu.program_counter, 0x8002);
+        assert_eq!(cpu.is_jammed(), true);
+        assert_eq!(cpu.jammed(), Some((0x8002, 0x02)));
+    }
+
+    #[test]
+    fn test_jammed_cpu_stays_jammed() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xe8, 0xf2, 0xe8], 0x8000).unwrap();
+        cpu.reset();
+        cpu.step().unwrap();
+        cpu.step().unwrap_err();
+        let cycles = cpu.cycles;
+
+        // Overwriting the KIL byte does not help, and NMIs are ignored.
+        cpu.mem_write(0x8001, 0xea);
+        cpu.set_nmi_line(true);
+        for _ in 0..3 {
+            assert_eq!(
+                cpu.step(),
+                Err(CpuError::Jammed {
+                    pc: 0x8001,
+                    opcode: 0xf2
+                })
+            );
+        }
+        assert_eq!(cpu.register_x, 1);
+        assert_eq!(cpu.cycles, cycles);
+        assert_eq!(cpu.jammed(), Some((0x8001, 0xf2)));
+    }
+
+    #[test]
+    fn test_reset_clears_jam() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0x12], 0x8000).unwrap();
+        cpu.reset();
+        cpu.step().unwrap_err();
+        cpu.load(&[0xe8], 0x8000).unwrap();
+
+        cpu.reset();
+
+        assert_eq!(cpu.is_jammed(), false);
+        assert_eq!(cpu.jammed(), None);
+        assert_eq!(cpu.step().unwrap().opcode, 0xe8);
+    }
+
+    #[test]
+    fn test_load_out_of_bounds() {
+        let mut cpu = CPU::new();
+        let result = cpu.load(&[0xea; 4], 0xfffd);
+
+        assert_eq!(
+            result,
+            Err(CpuError::OutOfBounds {
+                address: 0xfffd,
+                len: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_up_to_end_of_address_space() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xea; 3], 0xfffd).unwrap();
+
+        assert_eq!(cpu.mem_read(0xffff), 0xea);
+    }
+
+    #[test]
+    fn test_error_display() {
+        let error = CpuError::UnknownOpcode {
+            opcode: 0x8b,
+            pc: 0x8001,
+        };
+
+        assert_eq!(error.to_string(), "unknown opcode 0x8B at 0x8001");
+        let error = CpuError::Jammed {
+            pc: 0x8002,
+            opcode: 0x02,
+        };
+
+        assert_eq!(error.to_string(), "CPU jammed by opcode 0x02 at 0x8002");
+    }
+
+    #[test]
+    fn test_step_executes_one_instruction() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xa9, 0x05, 0xaa, 0x00], 0x8000).unwrap();
+        cpu.reset();
+
+        let result = cpu.step().unwrap();
+
+        assert_eq!(
+            result,
+            StepResult {
+                cycles: 2,
+                opcode: 0xa9,
+                interrupt_serviced: false
+            }
+        );
+        assert_eq!(cpu.register_a, 0x05);
+        assert_eq!(cpu.register_x, 0x00);
+        assert_eq!(cpu.program_counter, 0x8002);
+    }
+
+    #[test]
+    fn test_step_propagates_errors() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0x02], 0x8000).unwrap();
+        cpu.reset();
+
+        assert_eq!(
+            cpu.step(),
+            Err(CpuError::Jammed {
+                pc: 0x8000,
+                opcode: 0x02
+            })
+        );
+    }
+
+    #[test]
+    fn test_run_for_cycles() {
+        let mut cpu = CPU::new();
+        // INX; INX; INC $10; INX
+        cpu.load(&[0xe8, 0xe8, 0xe6, 0x10, 0xe8, 0x00], 0x8000)
+            .unwrap();
+        cpu.reset();
+
+        let elapsed = cpu.run_for_cycles(5).unwrap();
+
+        assert_eq!(elapsed, 9);
+        assert_eq!(cpu.register_x, 2);
+        assert_eq!(cpu.mem_read(0x0010), 1);
+        assert_eq!(cpu.program_counter, 0x8004);
+    }
+
+    #[test]
+    fn test_run_until() {
+        let mut cpu = CPU::new();
+        // loop: INX; JMP loop
+        cpu.load(&[0xe8, 0x4c, 0x00, 0x80], 0x8000).unwrap();
+        cpu.reset();
+
+        cpu.run_until(|cpu| cpu.register_x == 10).unwrap();
+
+        assert_eq!(cpu.register_x, 10);
+        assert_eq!(cpu.program_counter, 0x8001);
+    }
+
+    /// Published cycle counts of the official opcodes, without penalties.
+    #[rustfmt::skip]
+    const OFFICIAL_CYCLES: [(u8, u8); 151] = [
+        (0x69, 2), (0x65, 3), (0x75, 4), (0x6D, 4), (0x7D, 4), (0x79, 4), (0x61, 6), (0x71, 5),
+        (0x29, 2), (0x25, 3), (0x35, 4), (0x2D, 4), (0x3D, 4), (0x39, 4), (0x21, 6), (0x31, 5),
+        (0x0A, 2), (0x06, 5), (0x16, 6), (0x0E, 6), (0x1E, 7),
+        (0x90, 2), (0xB0, 2), (0xF0, 2), (0x30, 2), (0xD0, 2), (0x10, 2), (0x50, 2), (0x70, 2),
+        (0x24, 3), (0x2C, 4), (0x00, 7),
+        (0x18, 2), (0xD8, 2), (0x58, 2), (0xB8, 2),
+        (0xC9, 2), (0xC5, 3), (0xD5, 4), (0xCD, 4), (0xDD, 4), (0xD9, 4), (0xC1, 6), (0xD1, 5),
+        (0xE0, 2), (0xE4, 3), (0xEC, 4), (0xC0, 2), (0xC4, 3), (0xCC, 4),
+        (0xC6, 5), (0xD6, 6), (0xCE, 6), (0xDE, 7), (0xCA, 2), (0x88, 2),
+        (0x49, 2), (0x45, 3), (0x55, 4), (0x4D, 4), (0x5D, 4), (0x59, 4), (0x41, 6), (0x51, 5),
+        (0xE6, 5), (0xF6, 6), (0xEE, 6), (0xFE, 7), (0xE8, 2), (0xC8, 2),
+        (0x4C, 3), (0x6C, 5), (0x20, 6),
+        (0xA9, 2), (0xA5, 3), (0xB5, 4), (0xAD, 4), (0xBD, 4), (0xB9, 4), (0xA1, 6), (0xB1, 5),
+        (0xA2, 2), (0xA6, 3), (0xB6, 4), (0xAE, 4), (0xBE, 4),
+        (0xA0, 2), (0xA4, 3), (0xB4, 4), (0xAC, 4), (0xBC, 4),
+        (0x4A, 2), (0x46, 5), (0x56, 6), (0x4E, 6), (0x5E, 7), (0xEA, 2),
+        (0x09, 2), (0x05, 3), (0x15, 4), (0x0D, 4), (0x1D, 4), (0x19, 4), (0x01, 6), (0x11, 5),
+        (0x48, 3), (0x08, 3), (0x68, 4), (0x28, 4),
+        (0x2A, 2), (0x26, 5), (0x36, 6), (0x2E, 6), (0x3E, 7),
+        (0x6A, 2), (0x66, 5), (0x76, 6), (0x6E, 6), (0x7E, 7),
+        (0x40, 6), (0x60, 6),
+        (0xE9, 2), (0xE5, 3), (0xF5, 4), (0xED, 4), (0xFD, 4), (0xF9, 4), (0xE1, 6), (0xF1, 5),
+        (0x38, 2), (0xF8, 2), (0x78, 2),
+        (0x85, 3), (0x95, 4), (0x8D, 4), (0x9D, 5), (0x99, 5), (0x81, 6), (0x91, 6),
+        (0x86, 3), (0x96, 4), (0x8E, 4), (0x84, 3), (0x94, 4), (0x8C, 4),
+        (0xAA, 2), (0xA8, 2), (0xBA, 2), (0x8A, 2), (0x9A, 2), (0x98, 2),
+    ];
+
+    /// Runs `program` from 0x8000 for a single instruction.
+    fn step_once(cpu: &mut CPU, program: &[u8]) -> StepResult {
+        cpu.load(program, 0x8000).unwrap();
+        cpu.reset();
+        cpu.step().unwrap()
+    }
+
+    #[test]
+    fn test_official_opcode_cycles() {
+        for (opcode, expected) in OFFICIAL_CYCLES {
+            let mut cpu = CPU::new();
+            // Set every flag so no branch is taken; operands never cross a page.
+            cpu.load(&[opcode, 0x10, 0x02], 0x8000).unwrap();
+            cpu.reset();
+            cpu.status = Status::all();
+            if opcode == 0xB0 || opcode == 0xF0 || opcode == 0x30 || opcode == 0x70 {
+                cpu.status = Status::UNUSED;
+            }
+
+            let result = cpu.step().unwrap();
+
+            assert_eq!(result.cycles, expected, "0x{:02X}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_reset_takes_seven_cycles() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0x00], 0x8000).unwrap();
+        cpu.reset();
+
+        assert_eq!(cpu.cycles, 7);
+    }
+
+    #[test]
+    fn test_cycles_accumulate() {
+        let mut cpu = CPU::new();
+        // LDA #$01; STA $0200; INC $0200; BRK
+        cpu.load_and_run(&[0xa9, 0x01, 0x8d, 0x00, 0x02, 0xee, 0x00, 0x02, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.cycles, 7 + 2 + 4 + 6);
+    }
+
+    #[test]
+    fn test_absolute_x_read_page_cross_penalty() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xbd, 0xff, 0x02], 0x8000).unwrap();
+        cpu.reset();
+        cpu.register_x = 1;
+
+        assert_eq!(cpu.step().unwrap().cycles, 5);
+    }
+
+    #[test]
+    fn test_absolute_y_read_page_cross_penalty() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xb9, 0x80, 0x02], 0x8000).unwrap();
+        cpu.reset();
+        cpu.register_y = 0x80;
+
+        assert_eq!(cpu.step().unwrap().cycles, 5);
+    }
+
+    #[test]
+    fn test_indirect_y_read_page_cross_penalty() {
+        let mut cpu = CPU::new();
+        cpu.mem_write_u16(0x0010, 0x02ff);
+        cpu.load(&[0xb1, 0x10], 0x8000).unwrap();
+        cpu.reset();
+        cpu.register_y = 1;
+
+        assert_eq!(cpu.step().unwrap().cycles, 6);
+    }
+
+    #[test]
+    fn test_store_has_no_page_cross_penalty() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0x9d, 0xff, 0x02], 0x8000).unwrap();
+        cpu.reset();
+        cpu.register_x = 1;
+
+        assert_eq!(cpu.step().unwrap().cycles, 5);
+    }
+
+    #[test]
+    fn test_read_modify_write_has_no_page_cross_penalty() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xfe, 0xff, 0x02], 0x8000).unwrap();
+        cpu.reset();
+        cpu.register_x = 1;
+
+        assert_eq!(cpu.step().unwrap().cycles, 7);
+    }
+
+    #[test]
+    fn test_zero_page_x_never_crosses_page() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xb5, 0xff], 0x8000).unwrap();
+        cpu.reset();
+        cpu.register_x = 1;
+
+        assert_eq!(cpu.step().unwrap().cycles, 4);
+    }
+
+    #[test]
+    fn test_branch_taken_same_page() {
+        let mut cpu = CPU::new();
+        // BNE +$10
+        assert_eq!(step_once(&mut cpu, &[0xd0, 0x10]).cycles, 3);
+    }
+
+    #[test]
+    fn test_branch_taken_across_page() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xd0, 0x10], 0x80f0).unwrap();
+        cpu.reset();
+
+        assert_eq!(cpu.step().unwrap().cycles, 4);
+        assert_eq!(cpu.program_counter, 0x8102);
+    }
+
+    #[test]
+    fn test_branch_taken_backwards_across_page() {
+        let mut cpu = CPU::new();
+        // BNE -$10
+        assert_eq!(step_once(&mut cpu, &[0xd0, 0xf0]).cycles, 4);
+        assert_eq!(cpu.program_counter, 0x7ff2);
+    }
+
+    #[test]
+    fn test_branch_not_taken() {
+        let mut cpu = CPU::new();
+        // BEQ +$10
+        assert_eq!(step_once(&mut cpu, &[0xf0, 0x10]).cycles, 2);
+    }
+
+    /// Loads `program` at 0x8000 with the NMI handler at 0x9000 and the
+    /// IRQ/BRK handler at 0xA000, each of them `INY; RTI`.
+    fn cpu_with_handlers(program: &[u8]) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xc8, 0x40], 0x9000).unwrap();
+        cpu.load(&[0xc8, 0x40], 0xa000).unwrap();
+        cpu.load(program, 0x8000).unwrap();
+        cpu.mem_write_u16(0xfffa, 0x9000);
+        cpu.mem_write_u16(0xfffe, 0xa000);
+        cpu.reset();
+        cpu
+    }
+
+    #[test]
+    fn test_brk_jumps_through_irq_vector() {
+        let mut cpu = cpu_with_handlers(&[0x00, 0xff, 0xe8]);
+
+        let result = cpu.step().unwrap();
+
+        assert_eq!(result.cycles, 7);
+        assert_eq!(result.interrupt_serviced, false);
+        assert_eq!(cpu.program_counter, 0xa000);
+        assert_eq!(cpu.stack_pointer, 0xfa);
+        assert_eq!(cpu.mem_read_u16(0x01fc), 0x8002);
+        assert_eq!(cpu.mem_read(0x01fb), 0b0011_0100);
+        assert_eq!(cpu.status.contains(Status::INTERRUPT_DISABLE), true);
+    }
+
+    #[test]
+    fn test_brk_rti_skips_padding_byte() {
+        let mut cpu = cpu_with_handlers(&[0x00, 0xff, 0xe8]);
+
+        for _ in 0..4 {
+            cpu.step().unwrap();
+        }
+
+        assert_eq!(cpu.register_y, 1);
+        assert_eq!(cpu.register_x, 1);
+        assert_eq!(cpu.stack_pointer, 0xfd);
+    }
+
+    #[test]
+    fn test_brk_ignores_interrupt_disable() {
+        let mut cpu = cpu_with_handlers(&[0x78, 0x00, 0xff]);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.program_counter, 0xa000);
+    }
+
+    #[test]
+    fn test_nmi_is_serviced_after_the_next_instruction() {
+        let mut cpu = cpu_with_handlers(&[0xe8, 0xe8, 0xe8]);
+        cpu.step().unwrap();
+        cpu.set_nmi_line(true);
+
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, false);
+        let result = cpu.step().unwrap();
+
+        assert_eq!(
+            result,
+            StepResult {
+                cycles: 7,
+                opcode: 0x00,
+                interrupt_serviced: true
+            }
+        );
+        assert_eq!(cpu.register_x, 2);
+        assert_eq!(cpu.program_counter, 0x9000);
+        assert_eq!(cpu.mem_read_u16(0x01fc), 0x8002);
+        assert_eq!(cpu.mem_read(0x01fb), 0b0010_0100);
+        assert_eq!(cpu.cycles, 7 + 2 + 2 + 7);
+    }
+
+    #[test]
+    fn test_nmi_ignores_interrupt_disable() {
+        let mut cpu = cpu_with_handlers(&[0x78, 0xe8]);
+        cpu.set_nmi_line(true);
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, true);
+        assert_eq!(cpu.program_counter, 0x9000);
+    }
+
+    #[test]
+    fn test_nmi_is_edge_triggered() {
+        let mut cpu = cpu_with_handlers(&[0xe8, 0xe8, 0xe8, 0xe8]);
+        cpu.set_nmi_line(true);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.program_counter, 0x8001);
+
+        // Back from the handler with the line still held: no second NMI.
+        cpu.set_nmi_line(true);
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, false);
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, false);
+        assert_eq!(cpu.register_x, 3);
+
+        cpu.set_nmi_line(false);
+        cpu.set_nmi_line(true);
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, true);
+        assert_eq!(cpu.register_y, 1);
+    }
+
+    #[test]
+    fn test_irq_is_masked_by_interrupt_disable() {
+        let mut cpu = cpu_with_handlers(&[0xe8, 0xe8, 0x58, 0xe8, 0xe8]);
+        cpu.set_irq_line(true);
+
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, false);
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, false);
+        assert_eq!(cpu.register_x, 2);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, true);
+        assert_eq!(cpu.program_counter, 0xa000);
+        assert_eq!(cpu.mem_read(0x01fb) & 0b0011_0000, 0b0010_0000);
+    }
+
+    #[test]
+    fn test_irq_is_level_triggered() {
+        let mut cpu = cpu_with_handlers(&[0x58, 0xea, 0xe8, 0xe8, 0xe8]);
+        cpu.step().unwrap();
+        cpu.set_irq_line(true);
+
+        // NOP, IRQ, INY, RTI and straight back into the handler.
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, true);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, true);
+        assert_eq!(cpu.register_y, 1);
+        assert_eq!(cpu.register_x, 0);
+
+        cpu.set_irq_line(false);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.register_y, 2);
+        assert_eq!(cpu.register_x, 1);
+    }
+
+    #[test]
+    fn test_nmi_takes_priority_over_irq() {
+        let mut cpu = cpu_with_handlers(&[0x58, 0xe8, 0xe8]);
+        cpu.step().unwrap();
+        cpu.set_irq_line(true);
+        cpu.set_nmi_line(true);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.program_counter, 0x9000);
+    }
+
+    #[test]
+    fn test_cli_delays_irq_by_one_instruction() {
+        let mut cpu = cpu_with_handlers(&[0x58, 0xe8, 0xe8]);
+        cpu.set_irq_line(true);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, false);
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, true);
+        assert_eq!(cpu.register_x, 1);
+        assert_eq!(cpu.mem_read_u16(0x01fc), 0x8002);
+    }
+
+    #[test]
+    fn test_sei_lets_one_irq_through() {
+        let mut cpu = cpu_with_handlers(&[0x58, 0xea, 0x78, 0xe8]);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.set_irq_line(true);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, true);
+        assert_eq!(cpu.mem_read_u16(0x01fc), 0x8003);
+        // The handler sees I already set in the pushed status.
+        assert_eq!(cpu.mem_read(0x01fb), 0b0010_0100);
+    }
+
+    #[test]
+    fn test_plp_delays_irq_by_one_instruction() {
+        let mut cpu = cpu_with_handlers(&[0xa9, 0x00, 0x48, 0x28, 0xe8, 0xe8]);
+        cpu.set_irq_line(true);
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.status.contains(Status::INTERRUPT_DISABLE), false);
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, false);
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, true);
+        assert_eq!(cpu.register_x, 1);
+    }
+
+    #[test]
+    fn test_plp_setting_i_lets_one_irq_through() {
+        let mut cpu = cpu_with_handlers(&[0x58, 0xa9, 0x04, 0x48, 0x28, 0xe8]);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.set_irq_line(true);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, true);
+        assert_eq!(cpu.register_x, 0);
+    }
+
+    #[test]
+    fn test_rti_restores_i_without_delay() {
+        // BRK pushes P with I clear and the handler returns straight away.
+        let mut cpu = cpu_with_handlers(&[0x00, 0xff, 0xe8, 0xe8]);
+        cpu.load(&[0x40], 0xa000).unwrap();
+        cpu.status.remove(Status::INTERRUPT_DISABLE);
+        cpu.step().unwrap();
+        cpu.set_irq_line(true);
+
+        // RTI pulls I clear before its poll, so the IRQ is taken right away.
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, true);
+        assert_eq!(cpu.register_x, 0);
+    }
+
+    #[test]
+    fn test_nmi_hijacks_brk() {
+        let mut cpu = cpu_with_handlers(&[0x00, 0xff, 0xe8]);
+        cpu.set_nmi_line(true);
+
+        let result = cpu.step().unwrap();
+
+        assert_eq!(result.opcode, 0x00);
+        assert_eq!(result.interrupt_serviced, false);
+        assert_eq!(cpu.program_counter, 0x9000);
+        assert_eq!(cpu.mem_read_u16(0x01fc), 0x8002);
+        assert_eq!(cpu.mem_read(0x01fb), 0b0011_0100);
+
+        // The hijacking NMI is consumed: INY, RTI and back after the BRK.
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, false);
+        assert_eq!(cpu.register_x, 1);
+    }
+
+    #[test]
+    fn test_nmi_hijacks_irq() {
+        let mut cpu = cpu_with_handlers(&[0x58, 0xe8, 0xe8]);
+        cpu.step().unwrap();
+        cpu.set_irq_line(true);
+        cpu.step().unwrap();
+        cpu.set_nmi_line(true);
+
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, true);
+
+        assert_eq!(cpu.program_counter, 0x9000);
+        assert_eq!(cpu.mem_read(0x01fb) & 0b0001_0000, 0);
+    }
+
+    #[test]
+    fn test_reset_clears_pending_nmi() {
+        let mut cpu = cpu_with_handlers(&[0xe8]);
+        cpu.set_nmi_line(true);
+        cpu.reset();
+
+        assert_eq!(cpu.step().unwrap().interrupt_serviced, false);
+    }
+
+    #[test]
+    fn test_lax_loads_a_and_x() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x85);
+        cpu.load_and_run(&[0xa7, 0x10, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_a, 0x85);
+        assert_eq!(cpu.register_x, 0x85);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_sax_stores_a_and_x() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0xf0, 0xa2, 0x3c, 0x87, 0x10, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.mem_read(0x10), 0x30);
+        assert_eq!(cpu.status.contains(Status::ZERO), false);
+    }
+
+    #[test]
+    fn test_dcp_decrements_then_compares() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x43);
+        cpu.load_and_run(&[0xa9, 0x42, 0xc7, 0x10, 0x00]).unwrap();
+
+        assert_eq!(cpu.mem_read(0x10), 0x42);
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_isb_increments_then_subtracts() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x0f);
+        cpu.load_and_run(&[0xa9, 0x20, 0x38, 0xe7, 0x10, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.mem_read(0x10), 0x10);
+        assert_eq!(cpu.register_a, 0x10);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_slo_shifts_then_ors() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x41);
+        cpu.load_and_run(&[0xa9, 0x02, 0x07, 0x10, 0x00]).unwrap();
+
+        assert_eq!(cpu.mem_read(0x10), 0x82);
+        assert_eq!(cpu.register_a, 0x82);
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_rla_rotates_then_ands() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x80);
+        cpu.load_and_run(&[0xa9, 0xff, 0x38, 0x27, 0x10, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.mem_read(0x10), 0x01);
+        assert_eq!(cpu.register_a, 0x01);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_sre_shifts_then_eors() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x03);
+        cpu.load_and_run(&[0xa9, 0x10, 0x47, 0x10, 0x00]).unwrap();
+
+        assert_eq!(cpu.mem_read(0x10), 0x01);
+        assert_eq!(cpu.register_a, 0x11);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_rra_rotates_then_adds() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x02);
+        cpu.load_and_run(&[0xa9, 0x01, 0x38, 0x67, 0x10, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.mem_read(0x10), 0x81);
+        assert_eq!(cpu.register_a, 0x82);
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+    }
+
+    #[test]
+    fn test_anc_copies_negative_into_carry() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0xf0, 0x0b, 0x80, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_a, 0x80);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_alr_ands_then_shifts() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0xff, 0x4b, 0x03, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_a, 0x01);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_arr_sets_carry_and_overflow_from_bits_6_and_5() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0xff, 0x38, 0x6b, 0xff, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.register_a, 0xff);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+        assert_eq!(cpu.status.contains(Status::OVERFLOW), false);
+
+        cpu.load_and_run(&[0xa9, 0xff, 0x18, 0x6b, 0x40, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.register_a, 0x20);
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+        assert_eq!(cpu.status.contains(Status::OVERFLOW), true);
+    }
+
+    #[test]
+    fn test_axs_subtracts_from_a_and_x() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0xf0, 0xa2, 0x3c, 0xcb, 0x10, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.register_x, 0x20);
+        assert_eq!(cpu.register_a, 0xf0);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_unofficial_sbc_immediate() {
+        let mut cpu = CPU::new();
+        cpu.load_and_run(&[0xa9, 0x10, 0x38, 0xeb, 0x01, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.register_a, 0x0f);
+    }
+
+    #[test]
+    fn test_multi_byte_nops_skip_operands() {
+        let mut cpu = CPU::new();
+        cpu.load(
+            &[
+                0x1a, 0x80, 0xff, 0x04, 0x10, 0x0c, 0x00, 0x02, 0xa2, 0x01, 0x1c, 0xff, 0x80,
+            ],
+            0x8000,
+        )
+        .unwrap();
+        cpu.reset();
+
+        let cycles: Vec<u8> = (0..6).map(|_| cpu.step().unwrap().cycles).collect();
+
+        assert_eq!(cycles, vec![2, 2, 3, 4, 2, 5]);
+        assert_eq!(cpu.program_counter, 0x800d);
+        assert_eq!(cpu.register_a, 0);
+    }
+
+    #[test]
+    fn test_unofficial_read_pays_page_cross_but_rmw_does_not() {
+        let mut cpu = CPU::new();
+        cpu.mem_write(0x10, 0x01);
+        cpu.mem_write(0x11, 0x02);
+        cpu.load(&[0xb3, 0x10], 0x8000).unwrap();
+        cpu.reset();
+        cpu.register_y = 0xff;
+        assert_eq!(cpu.step().unwrap().cycles, 6);
+
+        let mut cpu = CPU::new();
+        cpu.load(&[0xdb, 0x01, 0x02], 0x8000).unwrap();
+        cpu.reset();
+        cpu.register_y = 0xff;
+        assert_eq!(cpu.step().unwrap().cycles, 7);
+    }
+
+    #[test]
+    fn test_unstable_opcodes_are_rejected_by_default() {
+        let mut cpu = CPU::new();
+        let result = cpu.load_and_run(&[0xab, 0xff, 0x00]);
+
+        assert_eq!(
+            result,
+            Err(CpuError::UnknownOpcode {
+                opcode: 0xab,
+                pc: 0x8000
+            })
+        );
+    }
+
+    #[test]
+    fn test_xaa_and_lxa_use_the_magic_constant() {
+        let mut cpu = CPU::new();
+        cpu.unstable_opcodes = UnstableOpcodes::Emulate { magic: 0xee };
+        cpu.load_and_run(&[0xa9, 0xff, 0xa2, 0x0f, 0x8b, 0x3c, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.register_a, 0x0c);
+
+        cpu.load_and_run(&[0xab, 0xff, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_a, 0xee);
+        assert_eq!(cpu.register_x, 0xee);
+    }
+
+    #[test]
+    fn test_las_ands_memory_with_stack_pointer() {
+        let mut cpu = CPU::new();
+        cpu.unstable_opcodes = UnstableOpcodes::Emulate { magic: 0xee };
+        cpu.mem_write(0x0200, 0x3f);
+        cpu.load_and_run(&[0xbb, 0x00, 0x02, 0x00]).unwrap();
+
+        assert_eq!(cpu.register_a, 0x3d);
+        assert_eq!(cpu.register_x, 0x3d);
+        assert_eq!(cpu.stack_pointer, 0x3d);
+    }
+
+    #[test]
+    fn test_shx_ands_with_high_byte_plus_one() {
+        let mut cpu = CPU::new();
+        cpu.unstable_opcodes = UnstableOpcodes::Emulate { magic: 0xee };
+        cpu.load_and_run(&[0xa2, 0xff, 0xa0, 0x01, 0x9e, 0x00, 0x02, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.mem_read(0x0201), 0x03);
+    }
+
+    #[test]
+    fn test_shx_page_cross_corrupts_address() {
+        let mut cpu = CPU::new();
+        cpu.unstable_opcodes = UnstableOpcodes::Emulate { magic: 0xee };
+        cpu.load_and_run(&[0xa2, 0x01, 0xa0, 0x10, 0x9e, 0xf8, 0x02, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.mem_read(0x0308), 0x00);
+        assert_eq!(cpu.mem_read(0x0108), 0x01);
+    }
+
+    #[test]
+    fn test_tas_sets_stack_pointer_and_stores() {
+        let mut cpu = CPU::new();
+        cpu.unstable_opcodes = UnstableOpcodes::Emulate { magic: 0xee };
+        cpu.load_and_run(&[0xa9, 0xf3, 0xa2, 0x3f, 0xa0, 0x04, 0x9b, 0x00, 0x02, 0x00])
+            .unwrap();
+
+        assert_eq!(cpu.stack_pointer, 0x33);
+        assert_eq!(cpu.mem_read(0x0204), 0x03);
+    }
+
+    fn cpu_with_variant(variant: Variant, program: &[u8]) -> CPU {
+        let mut cpu = CPU::new();
+        cpu.variant = variant;
+        cpu.load(program, 0x8000).unwrap();
+        cpu.reset();
+        cpu
+    }
+
+    #[test]
+    fn test_2a03_ignores_decimal_mode() {
+        let mut cpu = cpu_with_variant(Variant::Ricoh2A03, &[0xf8, 0xa9, 0x19, 0x69, 0x28]);
+        cpu.run_for_cycles(6).unwrap();
+
+        assert_eq!(cpu.register_a, 0x41);
+        assert_eq!(cpu.status.contains(Status::DECIMAL), true);
+    }
+
+    #[test]
+    fn test_nmos_decimal_adc() {
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &[0xf8, 0xa9, 0x19, 0x69, 0x28]);
+        cpu.run_for_cycles(6).unwrap();
+
+        assert_eq!(cpu.register_a, 0x47);
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &[0xf8, 0xa9, 0x99, 0x69, 0x01]);
+        cpu.run_for_cycles(6).unwrap();
+
+        assert_eq!(cpu.register_a, 0x00);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_nmos_decimal_sbc() {
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &[0xf8, 0x38, 0xa9, 0x42, 0xe9, 0x13]);
+        cpu.run_for_cycles(8).unwrap();
+
+        assert_eq!(cpu.register_a, 0x29);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &[0xf8, 0x38, 0xa9, 0x00, 0xe9, 0x01]);
+        cpu.run_for_cycles(8).unwrap();
+
+        assert_eq!(cpu.register_a, 0x99);
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+    }
+
+    #[test]
+    fn test_nmos_decimal_arr() {
+        // SED; LDA #$FF; CLC; ARR #$FF
+        let program = [0xf8, 0xa9, 0xff, 0x18, 0x6b, 0xff];
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &program);
+        cpu.run_for_cycles(8).unwrap();
+
+        assert_eq!(cpu.register_a, 0xd5);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), false);
+        assert_eq!(cpu.status.contains(Status::ZERO), false);
+        assert_eq!(cpu.status.contains(Status::OVERFLOW), false);
+
+        // The flags come from the rotated value, before it is corrected.
+        let program = [0xf8, 0xa9, 0x01, 0x38, 0x6b, 0x01];
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &program);
+        cpu.run_for_cycles(8).unwrap();
+
+        assert_eq!(cpu.register_a, 0x80);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+
+        let mut cpu = cpu_with_variant(Variant::Ricoh2A03, &[0xf8, 0xa9, 0xff, 0x18, 0x6b, 0xff]);
+        cpu.run_for_cycles(8).unwrap();
+
+        assert_eq!(cpu.register_a, 0x7f);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_nmos_alr_anc_and_axs_ignore_decimal_mode() {
+        // SED; LDA #$FF; ALR #$FE; ANC #$FF; LDX #$FF; AXS #$09
+        let program = [
+            0xf8, 0xa9, 0xff, 0x4b, 0xfe, 0x0b, 0xff, 0xa2, 0xff, 0xcb, 0x09,
+        ];
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &program);
+        cpu.run_for_cycles(8).unwrap();
+        assert_eq!(cpu.register_a, 0x7f);
+
+        cpu.run_for_cycles(2).unwrap();
+        assert_eq!(cpu.status.contains(Status::CARRY), false);
+
+        cpu.run_for_cycles(4).unwrap();
+        assert_eq!(cpu.register_x, 0x76);
+        assert_eq!(cpu.status.contains(Status::CARRY), true);
+    }
+
+    #[test]
+    fn test_nmos_jmp_indirect_wraps_within_page() {
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &[0x6c, 0xff, 0x02]);
+        cpu.mem_write(0x02ff, 0x08);
+        cpu.mem_write(0x0200, 0x40);
+        cpu.mem_write(0x0300, 0x80);
+
+        assert_eq!(cpu.step().unwrap().cycles, 5);
+        assert_eq!(cpu.program_counter, 0x4008);
+    }
+
+    #[test]
+    fn test_65c02_jmp_indirect_crosses_page() {
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[0x6c, 0xff, 0x02]);
+        cpu.mem_write(0x02ff, 0x08);
+        cpu.mem_write(0x0200, 0x40);
+        cpu.mem_write(0x0300, 0x80);
+
+        assert_eq!(cpu.step().unwrap().cycles, 6);
+        assert_eq!(cpu.program_counter, 0x8008);
+    }
+
+    #[test]
+    fn test_65c02_jmp_absolute_indexed_indirect() {
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[0xa2, 0x02, 0x7c, 0x00, 0x02]);
+        cpu.mem_write_u16(0x0202, 0x8010);
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.step().unwrap().cycles, 6);
+        assert_eq!(cpu.program_counter, 0x8010);
+    }
+
+    #[test]
+    fn test_65c02_bra() {
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[0x80, 0x02, 0xe8, 0xe8, 0xe8]);
+
+        assert_eq!(cpu.step().unwrap().cycles, 3);
+        assert_eq!(cpu.program_counter, 0x8004);
+    }
+
+    #[test]
+    fn test_65c02_push_and_pull_x_and_y() {
+        // LDX #$42; PHX; PLY; LDY #$80; PHY; PLX
+        let mut cpu = cpu_with_variant(
+            Variant::Cmos65C02,
+            &[0xa2, 0x42, 0xda, 0x7a, 0xa0, 0x80, 0x5a, 0xfa, 0x00],
+        );
+        cpu.interpret().unwrap();
+
+        assert_eq!(cpu.register_y, 0x80);
+        assert_eq!(cpu.register_x, 0x80);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+        assert_eq!(cpu.stack_pointer, 0xfd);
+        assert_eq!(cpu.mem_read(0x01fd), 0x80);
+    }
+
+    #[test]
+    fn test_65c02_stz() {
+        let mut cpu = cpu_with_variant(
+            Variant::Cmos65C02,
+            &[0xa2, 0x10, 0x64, 0x10, 0x9e, 0x00, 0x02, 0x00],
+        );
+        cpu.mem_write(0x0010, 0xff);
+        cpu.mem_write(0x0210, 0xff);
+        cpu.interpret().unwrap();
+
+        assert_eq!(cpu.mem_read(0x0010), 0x00);
+        assert_eq!(cpu.mem_read(0x0210), 0x00);
+    }
+
+    #[test]
+    fn test_65c02_tsb_and_trb() {
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[0xa9, 0x0f, 0x04, 0x10, 0x00]);
+        cpu.mem_write(0x0010, 0x3c);
+        cpu.interpret().unwrap();
+
+        assert_eq!(cpu.mem_read(0x0010), 0x3f);
+        assert_eq!(cpu.status.contains(Status::ZERO), false);
+
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[0xa9, 0x0f, 0x1c, 0x00, 0x02, 0x00]);
+        cpu.mem_write(0x0200, 0xf0);
+        cpu.interpret().unwrap();
+
+        assert_eq!(cpu.mem_read(0x0200), 0xf0);
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+    }
+
+    #[test]
+    fn test_65c02_inc_and_dec_accumulator() {
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[0xa9, 0xff, 0x1a, 0x00]);
+        cpu.interpret().unwrap();
+
+        assert_eq!(cpu.register_a, 0x00);
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[0xa9, 0x00, 0x3a, 0x00]);
+        cpu.interpret().unwrap();
+
+        assert_eq!(cpu.register_a, 0xff);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_65c02_bit_immediate_only_sets_zero() {
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[0xa9, 0xf0, 0x89, 0x0f, 0x00]);
+        cpu.interpret().unwrap();
+
+        assert_eq!(cpu.status.contains(Status::ZERO), true);
+        assert_eq!(cpu.status.contains(Status::NEGATIVE), true);
+        assert_eq!(cpu.status.contains(Status::OVERFLOW), false);
+    }
+
+    #[test]
+    fn test_65c02_zero_page_indirect() {
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[0xb2, 0x10, 0x92, 0x12]);
+        cpu.mem_write_u16(0x0010, 0x0200);
+        cpu.mem_write_u16(0x0012, 0x0300);
+        cpu.mem_write(0x0200, 0x42);
+
+        assert_eq!(cpu.step().unwrap().cycles, 5);
+        assert_eq!(cpu.register_a, 0x42);
+        assert_eq!(cpu.step().unwrap().cycles, 5);
+        assert_eq!(cpu.mem_read(0x0300), 0x42);
+    }
+
+    #[test]
+    fn test_65c02_undefined_opcodes_are_nops() {
+        // (opcode, length, cycles)
+        let nops = [
+            (0x02, 2, 2),
+            (0x03, 1, 1),
+            (0x44, 2, 3),
+            (0x54, 2, 4),
+            (0x5c, 3, 8),
+            (0xa7, 1, 1),
+            (0xdc, 3, 4),
+            (0xeb, 1, 1),
+        ];
+        for (opcode, length, cycles) in nops {
+            let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[opcode, 0x10, 0x02]);
+
+            assert_eq!(cpu.step().unwrap().cycles, cycles, "0x{:02X}", opcode);
+            assert_eq!(cpu.program_counter, 0x8000 + length, "0x{:02X}", opcode);
+            assert_eq!(cpu.register_a, 0);
+            assert_eq!(cpu.register_x, 0);
+        }
+        for opcode in 0..=0xff {
+            let decoded = Opcode::decode(opcode, Variant::Cmos65C02);
+            assert!(
+                !decoded.mnemonic.is_unofficial(),
+                "0x{:02X} should not decode to an NMOS-only opcode",
+                opcode
+            );
+        }
+    }
+
+    #[test]
+    fn test_65c02_interrupts_clear_decimal() {
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[0xf8, 0x00, 0xff]);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.status.contains(Status::DECIMAL), false);
+        assert_eq!(cpu.mem_read(0x01fb) & 0b0000_1000, 0b0000_1000);
+
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &[0xf8, 0x00, 0xff]);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.status.contains(Status::DECIMAL), true);
+    }
+
+    #[test]
+    fn test_65c02_decimal_adc_takes_an_extra_cycle() {
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[0xf8, 0x69, 0x19, 0x69, 0x01]);
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.step().unwrap().cycles, 3);
+        assert_eq!(cpu.register_a, 0x19);
+        assert_eq!(cpu.step().unwrap().cycles, 3);
+        assert_eq!(cpu.register_a, 0x20);
+    }
+
+    #[test]
+    fn test_65c02_shift_absolute_x_only_pays_for_page_cross() {
+        let mut cpu = cpu_with_variant(
+            Variant::Cmos65C02,
+            &[
+                0x1e, 0x00, 0x02, 0xa2, 0xff, 0x1e, 0x01, 0x02, 0xfe, 0x00, 0x02,
+            ],
+        );
+        let cycles: Vec<u8> = (0..4).map(|_| cpu.step().unwrap().cycles).collect();
+
+        assert_eq!(cycles, vec![6, 2, 7, 7]);
+
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &[0x1e, 0x00, 0x02]);
+        assert_eq!(cpu.step().unwrap().cycles, 7);
+    }
+
+    /// Runs one ADC or SBC immediate in decimal mode on an already reset CPU.
+    fn run_decimal(cpu: &mut CPU, opcode: u8, a: u8, value: u8, carry: bool) -> (u8, Status) {
+        cpu.mem_write(0x8000, opcode);
+        cpu.mem_write(0x8001, value);
+        cpu.program_counter = 0x8000;
+        cpu.register_a = a;
+        cpu.status = Status::UNUSED | Status::DECIMAL;
+        cpu.status.set(Status::CARRY, carry);
+        cpu.step().unwrap();
+        (cpu.register_a, cpu.status)
+    }
+
+    fn to_bcd(value: u8) -> u8 {
+        ((value / 10) << 4) | (value % 10)
+    }
+
+    /// NMOS ADC in decimal mode as measured on hardware: the result and C
+    /// from sequence 1, N and V from sequence 2, and Z from binary addition.
+    fn nmos_adc_reference(a: u8, b: u8, carry: bool) -> (u8, bool, bool, bool, bool) {
+        let carry = carry as i16;
+        let mut lo = (a & 0x0f) as i16 + (b & 0x0f) as i16 + carry;
+        if lo >= 0x0a {
+            lo = ((lo + 0x06) & 0x0f) + 0x10;
+        }
+        let mut sum = (a & 0xf0) as i16 + (b & 0xf0) as i16 + lo;
+        let signed = (a as i8 as i16 & !0x0f) + (b as i8 as i16 & !0x0f) + lo;
+        let negative = signed & 0x80 != 0;
+        let overflow = !(-128..=127).contains(&signed);
+        if sum >= 0xa0 {
+            sum += 0x60;
+        }
+        let zero = a.wrapping_add(b).wrapping_add(carry as u8) == 0;
+        (sum as u8, sum >= 0x100, zero, negative, overflow)
+    }
+
+    #[test]
+    fn test_decimal_adc_valid_bcd_matches_decimal_arithmetic() {
+        for variant in [Variant::Nmos6502, Variant::Cmos65C02] {
+            let mut cpu = cpu_with_variant(variant, &[]);
+            for a in 0..100 {
+                for b in 0..100 {
+                    for carry in [false, true] {
+                        let (result, status) =
+                            run_decimal(&mut cpu, 0x69, to_bcd(a), to_bcd(b), carry);
+                        let sum = a + b + carry as u8;
+
+                        assert_eq!(result, to_bcd(sum % 100), "{:?} {} + {}", variant, a, b);
+                        assert_eq!(status.contains(Status::CARRY), sum >= 100);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decimal_sbc_valid_bcd_matches_decimal_arithmetic() {
+        for variant in [Variant::Nmos6502, Variant::Cmos65C02] {
+            let mut cpu = cpu_with_variant(variant, &[]);
+            for a in 0..100 {
+                for b in 0..100 {
+                    for carry in [false, true] {
+                        let (result, status) =
+                            run_decimal(&mut cpu, 0xe9, to_bcd(a), to_bcd(b), carry);
+                        let difference = a as i16 - b as i16 - !carry as i16;
+
+                        assert_eq!(
+                            result,
+                            to_bcd(difference.rem_euclid(100) as u8),
+                            "{:?} {} - {}",
+                            variant,
+                            a,
+                            b
+                        );
+                        assert_eq!(status.contains(Status::CARRY), difference >= 0);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_nmos_decimal_adc_all_operands() {
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &[]);
+        for a in 0..=0xff {
+            for b in 0..=0xff {
+                for carry in [false, true] {
+                    let (result, status) = run_decimal(&mut cpu, 0x69, a, b, carry);
+                    let expected = nmos_adc_reference(a, b, carry);
+
+                    assert_eq!(
+                        (
+                            result,
+                            status.contains(Status::CARRY),
+                            status.contains(Status::ZERO),
+                            status.contains(Status::NEGATIVE),
+                            status.contains(Status::OVERFLOW)
+                        ),
+                        expected,
+                        "0x{:02X} + 0x{:02X} + {}",
+                        a,
+                        b,
+                        carry as u8
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_nmos_decimal_sbc_flags_are_binary() {
+        let mut nmos = cpu_with_variant(Variant::Nmos6502, &[]);
+        let mut binary = cpu_with_variant(Variant::Ricoh2A03, &[]);
+        for a in 0..=0xff {
+            for b in 0..=0xff {
+                for carry in [false, true] {
+                    let (_, status) = run_decimal(&mut nmos, 0xe9, a, b, carry);
+                    let (_, expected) = run_decimal(&mut binary, 0xe9, a, b, carry);
+
+                    assert_eq!(
+                        status, expected,
+                        "0x{:02X} - 0x{:02X} - {}",
+                        a, b, !carry as u8
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_65c02_decimal_flags_follow_result() {
+        let mut cpu = cpu_with_variant(Variant::Cmos65C02, &[]);
+        let mut binary = cpu_with_variant(Variant::Ricoh2A03, &[]);
+        for opcode in [0x69, 0xe9] {
+            for a in 0..=0xff {
+                for b in 0..=0xff {
+                    for carry in [false, true] {
+                        let (result, status) = run_decimal(&mut cpu, opcode, a, b, carry);
+
+                        assert_eq!(status.contains(Status::ZERO), result == 0);
+                        assert_eq!(status.contains(Status::NEGATIVE), result & 0x80 != 0);
+                        if opcode == 0xe9 {
+                            let (_, expected) = run_decimal(&mut binary, opcode, a, b, carry);
+                            assert_eq!(
+                                status & (Status::CARRY | Status::OVERFLOW),
+                                expected & (Status::CARRY | Status::OVERFLOW)
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_nmos_decimal_flag_quirks() {
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &[]);
+
+        // 99 + 1 = 00 with C set, but Z follows the binary sum 0x9A and N
+        // the uncorrected 0xA0.
+        let (result, status) = run_decimal(&mut cpu, 0x69, 0x99, 0x01, false);
+        assert_eq!(result, 0x00);
+        assert_eq!(
+            status,
+            Status::UNUSED | Status::DECIMAL | Status::CARRY | Status::NEGATIVE
+        );
+
+        // 0x85 + 0x7B is binary zero, so Z is set though A is 0x66.
+        let (result, status) = run_decimal(&mut cpu, 0x69, 0x85, 0x7b, false);
+        assert_eq!(result, 0x66);
+        assert_eq!(status.contains(Status::ZERO), true);
+
+        // 0x79 + 0x00 + C = 0x80: V set as for a signed overflow.
+        let (result, status) = run_decimal(&mut cpu, 0x69, 0x79, 0x00, true);
+        assert_eq!(result, 0x80);
+        assert_eq!(status.contains(Status::OVERFLOW), true);
+        assert_eq!(status.contains(Status::NEGATIVE), true);
+    }
+
+    #[test]
+    fn test_decimal_mode_needs_the_d_flag() {
+        let mut cpu = cpu_with_variant(Variant::Nmos6502, &[0x18, 0xa9, 0x19, 0x69, 0x28]);
+        cpu.run_for_cycles(6).unwrap();
+
+        assert_eq!(cpu.register_a, 0x41);
+    }
+
+    /// Runs the first instruction of `program` and returns its bus cycles.
+    fn bus_accesses<F>(program: &[u8], setup: F) -> Vec<BusAccess>
+    where
+        F: FnOnce(&mut CPU<RecordingBus>),
+    {
+        let mut cpu = CPU::with_bus(RecordingBus::new(Ram::new()));
+        cpu.load(program, 0x8000).unwrap();
+        cpu.reset();
+        setup(&mut cpu);
+        cpu.bus.accesses.clear();
+        cpu.step().unwrap();
+        cpu.bus.accesses
+    }
+
+    #[test]
+    fn test_implied_instruction_reads_next_byte() {
+        let accesses = bus_accesses(&[0xaa, 0xe8], |_| {});
+
+        assert_eq!(
+            accesses,
+            vec![BusAccess::Read(0x8000, 0xaa), BusAccess::Read(0x8001, 0xe8)]
+        );
+    }
+
+    #[test]
+    fn test_absolute_x_read_without_page_cross() {
+        let accesses = bus_accesses(&[0xbd, 0x00, 0x02], |cpu| {
+            cpu.register_x = 0x01;
+            cpu.mem_write(0x0201, 0x42);
+        });
+
+        assert_eq!(
+            accesses,
+            vec![
+                BusAccess::Read(0x8000, 0xbd),
+                BusAccess::Read(0x8001, 0x00),
+                BusAccess::Read(0x8002, 0x02),
+                BusAccess::Read(0x0201, 0x42),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_absolute_x_read_with_page_cross_reads_unfixed_address() {
+        let accesses = bus_accesses(&[0xbd, 0x01, 0x02], |cpu| {
+            cpu.register_x = 0xff;
+            cpu.mem_write(0x0200, 0x11);
+            cpu.mem_write(0x0300, 0x42);
+        });
+
+        assert_eq!(
+            accesses,
+            vec![
+                BusAccess::Read(0x8000, 0xbd),
+                BusAccess::Read(0x8001, 0x01),
+                BusAccess::Read(0x8002, 0x02),
+                BusAccess::Read(0x0200, 0x11),
+                BusAccess::Read(0x0300, 0x42),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_absolute_x_store_always_reads_first() {
+        let accesses = bus_accesses(&[0x9d, 0x00, 0x02], |cpu| {
+            cpu.register_a = 0x42;
+            cpu.register_x = 0x01;
+        });
+
+        assert_eq!(
+            accesses,
+            vec![
+                BusAccess::Read(0x8000, 0x9d),
+                BusAccess::Read(0x8001, 0x00),
+                BusAccess::Read(0x8002, 0x02),
+                BusAccess::Read(0x0201, 0x00),
+                BusAccess::Write(0x0201, 0x42),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indirect_y_read_with_page_cross() {
+        let accesses = bus_accesses(&[0xb1, 0x10], |cpu| {
+            cpu.register_y = 0x10;
+            cpu.mem_write_u16(0x0010, 0x02f8);
+            cpu.mem_write(0x0308, 0x42);
+        });
+
+        assert_eq!(
+            accesses,
+            vec![
+                BusAccess::Read(0x8000, 0xb1),
+                BusAccess::Read(0x8001, 0x10),
+                BusAccess::Read(0x0010, 0xf8),
+                BusAccess::Read(0x0011, 0x02),
+                BusAccess::Read(0x0208, 0x00),
+                BusAccess::Read(0x0308, 0x42),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zero_page_x_rmw_writes_back_unmodified_value() {
+        let accesses = bus_accesses(&[0x16, 0x10], |cpu| {
+            cpu.register_x = 0x05;
+            cpu.mem_write(0x0015, 0x41);
+        });
+
+        assert_eq!(
+            accesses,
+            vec![
+                BusAccess::Read(0x8000, 0x16),
+                BusAccess::Read(0x8001, 0x10),
+                BusAccess::Read(0x0010, 0x00),
+                BusAccess::Read(0x0015, 0x41),
+                BusAccess::Write(0x0015, 0x41),
+                BusAccess::Write(0x0015, 0x82),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_absolute_x_rmw_without_page_cross() {
+        let accesses = bus_accesses(&[0xfe, 0x00, 0x20], |cpu| {
+            cpu.register_x = 0x02;
+        });
+
+        assert_eq!(
+            accesses,
+            vec![
+                BusAccess::Read(0x8000, 0xfe),
+                BusAccess::Read(0x8001, 0x00),
+                BusAccess::Read(0x8002, 0x20),
+                BusAccess::Read(0x2002, 0x00),
+                BusAccess::Read(0x2002, 0x00),
+                BusAccess::Write(0x2002, 0x00),
+                BusAccess::Write(0x2002, 0x01),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indexed_indirect_reads_base_pointer() {
+        let accesses = bus_accesses(&[0xa1, 0x10], |cpu| {
+            cpu.register_x = 0x04;
+            cpu.mem_write_u16(0x0014, 0x0300);
+            cpu.mem_write(0x0300, 0x42);
+        });
+
+        assert_eq!(
+            accesses,
+            vec![
+                BusAccess::Read(0x8000, 0xa1),
+                BusAccess::Read(0x8001, 0x10),
+                BusAccess::Read(0x0010, 0x00),
+                BusAccess::Read(0x0014, 0x00),
+                BusAccess::Read(0x0015, 0x03),
+                BusAccess::Read(0x0300, 0x42),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stack_instruction_accesses() {
+        let accesses = bus_accesses(&[0x68, 0xea], |cpu| {
+            cpu.mem_write(0x01fe, 0x42);
+        });
+
+        assert_eq!(
+            accesses,
+            vec![
+                BusAccess::Read(0x8000, 0x68),
+                BusAccess::Read(0x8001, 0xea),
+                BusAccess::Read(0x01fd, 0x00),
+                BusAccess::Read(0x01fe, 0x42),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_jsr_and_rts_accesses() {
+        let accesses = bus_accesses(&[0x20, 0x34, 0x12], |_| {});
+
+        assert_eq!(
+            accesses,
+            vec![
+                BusAccess::Read(0x8000, 0x20),
+                BusAccess::Read(0x8001, 0x34),
+                BusAccess::Read(0x01fd, 0x00),
+                BusAccess::Write(0x01fd, 0x80),
+                BusAccess::Write(0x01fc, 0x02),
+                BusAccess::Read(0x8002, 0x12),
+            ]
+        );
+
+        let accesses = bus_accesses(&[0x60, 0xea], |cpu| {
+            cpu.stack_pointer = 0xfb;
+            cpu.mem_write_u16(0x01fc, 0x8002);
+        });
+
+        assert_eq!(
+            accesses,
+            vec![
+                BusAccess::Read(0x8000, 0x60),
+                BusAccess::Read(0x8001, 0xea),
+                BusAccess::Read(0x01fb, 0x00),
+                BusAccess::Read(0x01fc, 0x02),
+                BusAccess::Read(0x01fd, 0x80),
+                BusAccess::Read(0x8002, 0x00),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_taken_branch_across_page_reads_unfixed_pc() {
+        let mut program = vec![0xea; 0x100];
+        program[0xf0] = 0xd0;
+        program[0xf1] = 0x10;
+        let accesses = bus_accesses(&program, |cpu| cpu.program_counter = 0x80f0);
+
+        assert_eq!(
+            accesses,
+            vec![
+                BusAccess::Read(0x80f0, 0xd0),
+                BusAccess::Read(0x80f1, 0x10),
+                BusAccess::Read(0x80f2, 0xea),
+                BusAccess::Read(0x8002, 0xea),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interrupt_accesses() {
+        let mut cpu = CPU::with_bus(RecordingBus::new(Ram::new()));
+        cpu.load(&[0xea, 0xea], 0x8000).unwrap();
+        cpu.mem_write_u16(0xfffa, 0x9000);
+        cpu.reset();
+        cpu.set_nmi_line(true);
+        cpu.step().unwrap();
+        cpu.bus.accesses.clear();
+
+        cpu.step().unwrap();
+
+        assert_eq!(
+            cpu.bus.accesses,
+            vec![
+                BusAccess::Read(0x8001, 0xea),
+                BusAccess::Read(0x8001, 0xea),
+                BusAccess::Write(0x01fd, 0x80),
+                BusAccess::Write(0x01fc, 0x01),
+                BusAccess::Write(0x01fb, 0x24),
+                BusAccess::Read(0xfffa, 0x00),
+                BusAccess::Read(0xfffb, 0x90),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_every_nmos_opcode_makes_one_access_per_cycle() {
+        for opcode in 0..=0xff {
+            if Opcode::decode(opcode, Variant::Nmos6502).mnemonic == Mnemonic::JAM {
+                continue;
+            }
+            for index in [0x00, 0xff] {
+                let mut cpu = CPU::with_bus(RecordingBus::new(Ram::new()));
+                cpu.variant = Variant::Nmos6502;
+                cpu.unstable_opcodes = UnstableOpcodes::Emulate { magic: 0xee };
+                cpu.load(&[opcode, 0x80, 0x02], 0x8000).unwrap();
+                cpu.reset();
+                cpu.register_x = index;
+                cpu.register_y = index;
+                cpu.mem_write_u16(0x0080, 0x0280);
+                cpu.bus.accesses.clear();
+
+                let result = cpu.step().unwrap();
+
+                assert_eq!(
+                    cpu.bus.accesses.len(),
+                    result.cycles as usize,
+                    "0x{:02X} with X = Y = 0x{:02X}",
+                    opcode,
+                    index
+                );
+            }
+        }
+    }
+
+    /// A CPU about to run `opcode` with operand bytes 0x80 0x02, which
+    /// point (zp),Y and (zp,X) at 0x0280.
+    fn cpu_for_opcode(variant: Variant, opcode: u8, index: u8, status: u8) -> CPU<RecordingBus> {
+        let mut cpu = CPU::with_bus(RecordingBus::new(Ram::new()));
+        cpu.variant = variant;
+        cpu.unstable_opcodes = UnstableOpcodes::Emulate { magic: 0xee };
+        cpu.load(&[opcode, 0x80, 0x02], 0x8000).unwrap();
+        cpu.reset();
+        cpu.register_a = 0x5a;
+        cpu.register_x = index;
+        cpu.register_y = index;
+        cpu.status = Status::from_bits_retain(status);
+        cpu.mem_write_u16(0x0080, 0x0280);
+        cpu.bus.accesses.clear();
+        cpu
+    }
+
+    fn run_ticks<B: Bus>(cpu: &mut CPU<B>) -> StepResult {
+        loop {
+            if let Some(result) = cpu.tick().unwrap() {
+                return result;
+            }
+        }
+    }
+
+    #[test]
+    fn test_tick_matches_step_for_every_opcode() {
+        for variant in [Variant::Nmos6502, Variant::Cmos65C02] {
+            for opcode in 0..=0xff {
+                if Opcode::decode(opcode, variant).mnemonic == Mnemonic::JAM {
+                    continue;
+                }
+                for (index, status) in [(0x00, 0x24), (0xff, 0xef)] {
+                    let mut stepped = cpu_for_opcode(variant, opcode, index, status);
+                    let mut ticked = cpu_for_opcode(variant, opcode, index, status);
+
+                    let context = format!(
+                        "{:?} 0x{:02X} with X = Y = 0x{:02X}",
+                        variant, opcode, index
+                    );
+                    assert_eq!(
+                        run_ticks(&mut ticked),
+                        stepped.step().unwrap(),
+                        "{}",
+                        context
+                    );
+                    assert_eq!(
+                        (ticked.register_a, ticked.register_x, ticked.register_y),
+                        (stepped.register_a, stepped.register_x, stepped.register_y),
+                        "{}",
+                        context
+                    );
+                    assert_eq!(ticked.status, stepped.status, "{}", context);
+                    assert_eq!(ticked.stack_pointer, stepped.stack_pointer, "{}", context);
+                    assert_eq!(
+                        ticked.program_counter, stepped.program_counter,
+                        "{}",
+                        context
+                    );
+                    assert_eq!(ticked.cycles, stepped.cycles, "{}", context);
+                    assert_eq!(ticked.bus.accesses, stepped.bus.accesses, "{}", context);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_nmos_tick_makes_one_access() {
+        for opcode in 0..=0xff {
+            if Opcode::decode(opcode, Variant::Nmos6502).mnemonic == Mnemonic::JAM {
+                continue;
+            }
+            let mut cpu = cpu_for_opcode(Variant::Nmos6502, opcode, 0xff, 0x24);
+            loop {
+                let result = cpu.tick().unwrap();
+                assert_eq!(cpu.bus.accesses.len(), 1, "0x{:02X}", opcode);
+                cpu.bus.accesses.clear();
+                if result.is_some() {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tick_store_writes_on_its_last_cycle() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0xa9, 0x42, 0x8d, 0x00, 0x02], 0x8000).unwrap();
+        cpu.reset();
+        cpu.step().unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(cpu.tick().unwrap(), None);
+            assert_eq!(cpu.mem_read(0x0200), 0x00);
+        }
+        let result = cpu.tick().unwrap().unwrap();
+
+        assert_eq!(result.cycles, 4);
+        assert_eq!(result.opcode, 0x8d);
+        assert_eq!(cpu.mem_read(0x0200), 0x42);
+    }
+
+    #[test]
+    fn test_tick_runs_a_program_like_step() {
+        // Copies 0x8000..0x8010 to 0x0300 in a loop, then calls and returns.
+        let program = program(
+            "
+                    .org $8000
+                    ldx #$00
+            copy:   lda $8000,x
+                    sta $0300,x
+                    inx
+                    cpx #$10
+                    bne copy
+                    jsr sub
+                    nop
+                    brk
+                    .byte $00
+            sub:    php
+                    plp
+                    rts
+            ",
+        );
+        let mut stepped = CPU::new();
+        stepped.load(&program, 0x8000).unwrap();
+        stepped.reset();
+        let mut ticked = CPU::new();
+        ticked.load(&program, 0x8000).unwrap();
+        ticked.reset();
+
+        stepped.interpret().unwrap();
+        while ticked.bus.peek(ticked.program_counter) != 0x00 {
+            run_ticks(&mut ticked);
+        }
+
+        assert_eq!(ticked.program_counter, stepped.program_counter);
+        assert_eq!(ticked.register_x, 0x10);
+        assert_eq!(ticked.cycles, stepped.cycles);
+        for addr in 0x0300..0x0310 {
+            assert_eq!(ticked.mem_read(addr), stepped.mem_read(addr));
+        }
+    }
+
+    #[test]
+    fn test_tick_polls_nmi_raised_mid_instruction() {
+        let mut cpu = cpu_with_handlers(&[0xe8, 0xe8]);
+        cpu.tick().unwrap();
+        cpu.set_nmi_line(true);
+        cpu.tick().unwrap().unwrap();
+
+        let mut ticks = 0;
+        let result = loop {
+            ticks += 1;
+            if let Some(result) = cpu.tick().unwrap() {
+                break result;
+            }
+        };
+
+        assert_eq!(ticks, 7);
+        assert_eq!(result.interrupt_serviced, true);
+        assert_eq!(cpu.program_counter, 0x9000);
+        assert_eq!(cpu.mem_read_u16(0x01fc), 0x8001);
+    }
+
+    #[test]
+    fn test_tick_nmi_late_in_brk_hijacks_it() {
+        let mut cpu = cpu_with_handlers(&[0x00, 0xff, 0xe8]);
+        for _ in 0..5 {
+            cpu.tick().unwrap();
+        }
+        cpu.set_nmi_line(true);
+        run_ticks(&mut cpu);
+
+        assert_eq!(cpu.program_counter, 0x9000);
+        assert_eq!(cpu.mem_read(0x01fb), 0b0011_0100);
+    }
+
+    #[test]
+    fn test_step_finishes_an_instruction_started_by_tick() {
+        let mut cpu = cpu_with_handlers(&[0xad, 0x34, 0x12, 0xe8]);
+        cpu.mem_write(0x1234, 0x99);
+        cpu.tick().unwrap();
+        cpu.tick().unwrap();
+
+        let result = cpu.step().unwrap();
+
+        assert_eq!(result.opcode, 0xad);
+        assert_eq!(result.cycles, 4);
+        assert_eq!(cpu.register_a, 0x99);
+        assert_eq!(cpu.step().unwrap().opcode, 0xe8);
+    }
+
+    #[test]
+    fn test_65c02_tick_idles_after_its_last_access() {
+        let mut cpu = CPU::with_bus(RecordingBus::new(Ram::new()));
+        cpu.variant = Variant::Cmos65C02;
+        cpu.load(&[0x5c, 0x00, 0x00], 0x8000).unwrap();
+        cpu.reset();
+        cpu.bus.accesses.clear();
+
+        let result = run_ticks(&mut cpu);
+
+        assert_eq!(result.cycles, 8);
+        assert_eq!(cpu.bus.accesses.len(), 4);
+    }
+
+    #[test]
+    fn test_tick_reports_jam_without_counting_a_cycle() {
+        let mut cpu = CPU::new();
+        cpu.load(&[0x02], 0x8000).unwrap();
+        cpu.reset();
+
+        assert_eq!(
+            cpu.tick(),
+            Err(CpuError::Jammed {
+                pc: 0x8000,
+                opcode: 0x02
+            })
+        );
+        assert_eq!(cpu.cycles, 7);
+        assert_eq!(cpu.is_jammed(), true);
     }
 }
@@ -0,0 +1,249 @@
+use std::fmt;
+
+use crate::cpu::Variant;
+use crate::opcode::{AddressingMode, Opcode};
+
+/// One instruction decoded from machine code, or the bytes left over at
+/// the end of the input when they are too few for the opcode they start
+/// with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: u16,
+    pub opcode: Opcode,
+    /// The opcode byte and its operand, little-endian.
+    pub bytes: Vec<u8>,
+}
+
+impl Instruction {
+    /// Decodes the instruction at the start of `bytes`, which sits at
+    /// `address`, or returns `None` if `bytes` is empty. If `bytes` ends
+    /// before the operand does, the instruction is truncated.
+    pub fn decode(bytes: &[u8], address: u16, variant: Variant) -> Option<Instruction> {
+        let opcode = Opcode::decode(*bytes.first()?, variant);
+        let len = (opcode.len as usize).min(bytes.len());
+        Some(Instruction {
+            address,
+            opcode,
+            bytes: bytes[..len].to_vec(),
+        })
+    }
+
+    /// Whether the input ended before the operand did.
+    pub fn is_truncated(&self) -> bool {
+        self.bytes.len() < self.opcode.len as usize
+    }
+
+    /// The operand as a number: a byte, a word, or for branches the
+    /// address they go to. A truncated instruction has none.
+    pub fn operand(&self) -> Option<u16> {
+        if self.is_truncated() {
+            return None;
+        }
+        match self.bytes[1..] {
+            [] => None,
+            [offset] if self.opcode.mode == AddressingMode::Relative => Some(
+                self.address
+                    .wrapping_add(2)
+                    .wrapping_add(offset as i8 as u16),
+            ),
+            [byte] => Some(byte as u16),
+            [lo, hi] => Some(u16::from_le_bytes([lo, hi])),
+            _ => unreachable!("operands are at most two bytes"),
+        }
+    }
+
+    /// The operand in assembler syntax, e.g. `($80),Y` or `#$10`. It is
+    /// empty for implied instructions and, like `operand`, for truncated
+    /// ones.
+    pub fn operand_text(&self) -> String {
+        if self.opcode.mode == AddressingMode::Accumulator {
+            return "A".to_string();
+        }
+        let Some(value) = self.operand() else {
+            return String::new();
+        };
+        match self.opcode.mode {
+            AddressingMode::Immediate => format!("#${:02X}", value),
+            AddressingMode::ZeroPage => format!("${:02X}", value),
+            AddressingMode::ZeroPageX => format!("${:02X},X", value),
+            AddressingMode::ZeroPageY => format!("${:02X},Y", value),
+            AddressingMode::Absolute | AddressingMode::Relative => format!("${:04X}", value),
+            AddressingMode::AbsoluteX => format!("${:04X},X", value),
+            AddressingMode::AbsoluteY => format!("${:04X},Y", value),
+            AddressingMode::Indirect => format!("(${:04X})", value),
+            AddressingMode::IndirectX => format!("(${:02X},X)", value),
+            AddressingMode::IndirectY => format!("(${:02X}),Y", value),
+            AddressingMode::ZeroPageIndirect => format!("(${:02X})", value),
+            AddressingMode::AbsoluteIndirectX => format!("(${:04X},X)", value),
+            AddressingMode::Implied | AddressingMode::Accumulator => {
+                unreachable!("{:?} has no operand", self.opcode.mode)
+            }
+        }
+    }
+
+    /// The instruction in assembler syntax, with unofficial opcodes
+    /// marked by a leading `*` as in nestest.log, e.g. `*NOP $A9`. A
+    /// truncated instruction is shown as the data it is, `.byte $A9`.
+    pub fn text(&self) -> String {
+        if self.is_truncated() {
+            let bytes: Vec<String> = self.bytes.iter().map(|b| format!("${:02X}", b)).collect();
+            return format!(".byte {}", bytes.join(", "));
+        }
+        let marker = if self.opcode.official { "" } else { "*" };
+        let operand = self.operand_text();
+        if operand.is_empty() {
+            format!("{}{}", marker, self.opcode.mnemonic)
+        } else {
+            format!("{}{} {}", marker, self.opcode.mnemonic, operand)
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Formats the instruction like a listing, address and raw bytes first:
+    /// `C000  BD 00 02  LDA $0200,X`. The mnemonic of an official opcode
+    /// lines up with the `*` of an unofficial one.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes: Vec<String> = self.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        let text = self.text();
+        let pad = if text.starts_with('*') { "" } else { " " };
+        write!(
+            f,
+            "{:04X}  {:<8} {}{}",
+            self.address,
+            bytes.join(" "),
+            pad,
+            text
+        )
+    }
+}
+
+/// Decodes `bytes`, loaded at `base`, into consecutive instructions. Every
+/// byte is decoded as code; the last instruction may be truncated.
+pub fn disassemble(bytes: &[u8], base: u16, variant: Variant) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let address = base.wrapping_add(offset as u16);
+        let Some(instruction) = Instruction::decode(&bytes[offset..], address, variant) else {
+            break;
+        };
+        offset += instruction.bytes.len();
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn text(bytes: &[u8], address: u16, variant: Variant) -> String {
+        Instruction::decode(bytes, address, variant).unwrap().text()
+    }
+
+    #[test]
+    fn test_operands_in_every_nmos_addressing_mode() {
+        let cases: [(&[u8], &str); 13] = [
+            (&[0xEA], "NOP"),
+            (&[0x0A], "ASL A"),
+            (&[0xA9, 0x10], "LDA #$10"),
+            (&[0xA5, 0x80], "LDA $80"),
+            (&[0xB5, 0x80], "LDA $80,X"),
+            (&[0xB6, 0x80], "LDX $80,Y"),
+            (&[0xAD, 0x34, 0x12], "LDA $1234"),
+            (&[0xBD, 0x34, 0x12], "LDA $1234,X"),
+            (&[0xB9, 0x34, 0x12], "LDA $1234,Y"),
+            (&[0x6C, 0xFC, 0xFF], "JMP ($FFFC)"),
+            (&[0xA1, 0x80], "LDA ($80,X)"),
+            (&[0xB1, 0x80], "LDA ($80),Y"),
+            (&[0xD0, 0x02], "BNE $C004"),
+        ];
+
+        for (bytes, expected) in cases {
+            assert_eq!(text(bytes, 0xC000, Variant::Nmos6502), expected);
+        }
+    }
+
+    #[test]
+    fn test_65c02_addressing_modes() {
+        assert_eq!(text(&[0xB2, 0x80], 0, Variant::Cmos65C02), "LDA ($80)");
+        assert_eq!(
+            text(&[0x7C, 0x00, 0x20], 0, Variant::Cmos65C02),
+            "JMP ($2000,X)"
+        );
+        assert_eq!(text(&[0x80, 0x10], 0x0400, Variant::Cmos65C02), "BRA $0412");
+    }
+
+    #[test]
+    fn test_branch_targets_wrap_around_the_address_space() {
+        assert_eq!(text(&[0xF0, 0xFC], 0x0001, Variant::Nmos6502), "BEQ $FFFF");
+        assert_eq!(text(&[0x90, 0x7F], 0xFFF0, Variant::Nmos6502), "BCC $0071");
+    }
+
+    #[test]
+    fn test_unofficial_opcodes_are_marked() {
+        assert_eq!(text(&[0xA7, 0x10], 0, Variant::Nmos6502), "*LAX $10");
+        assert_eq!(text(&[0xEB, 0x01], 0, Variant::Nmos6502), "*SBC #$01");
+        assert_eq!(text(&[0x02], 0, Variant::Nmos6502), "*JAM");
+    }
+
+    #[test]
+    fn test_display_lines_up_like_a_listing() {
+        let program = [0xBD, 0x00, 0x02, 0x04, 0xA9, 0x60];
+        let lines: Vec<String> = disassemble(&program, 0xC000, Variant::Ricoh2A03)
+            .iter()
+            .map(|instruction| instruction.to_string())
+            .collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                "C000  BD 00 02  LDA $0200,X",
+                "C003  04 A9    *NOP $A9",
+                "C005  60        RTS",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncated_instruction_is_shown_as_data() {
+        let instructions = disassemble(&[0xEA, 0xAD, 0x34], 0x8000, Variant::Nmos6502);
+
+        assert_eq!(instructions.len(), 2);
+        assert!(instructions[1].is_truncated());
+        assert_eq!(instructions[1].operand(), None);
+        assert_eq!(
+            instructions[1].to_string(),
+            "8001  AD 34     .byte $AD, $34"
+        );
+    }
+
+    #[test]
+    fn test_decode_of_empty_or_short_input() {
+        assert_eq!(Instruction::decode(&[], 0x8000, Variant::Nmos6502), None);
+        assert_eq!(disassemble(&[], 0x8000, Variant::Nmos6502), vec![]);
+
+        let lda = Instruction::decode(&[0xA9], 0x8000, Variant::Nmos6502).unwrap();
+        assert!(lda.is_truncated());
+        assert_eq!(lda.bytes, vec![0xA9]);
+        assert_eq!(lda.operand(), None);
+        assert_eq!(lda.operand_text(), "");
+        assert_eq!(lda.text(), ".byte $A9");
+
+        let lsr = Instruction::decode(&[0x4A], 0x8000, Variant::Nmos6502).unwrap();
+        assert!(!lsr.is_truncated());
+        assert_eq!(lsr.operand_text(), "A");
+    }
+
+    #[test]
+    fn test_operand_values() {
+        let jmp = Instruction::decode(&[0x4C, 0xF5, 0xC5], 0xC000, Variant::Nmos6502).unwrap();
+        assert_eq!(jmp.operand(), Some(0xC5F5));
+
+        let tax = Instruction::decode(&[0xAA], 0xC000, Variant::Nmos6502).unwrap();
+        assert_eq!(tax.operand(), None);
+        assert_eq!(tax.operand_text(), "");
+    }
+}
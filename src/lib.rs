@@ -0,0 +1,7 @@
+pub mod asm;
+pub mod bus;
+pub mod cpu;
+pub mod disasm;
+pub mod opcode;
+pub mod status;
+pub mod trace;
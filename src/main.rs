@@ -1,9 +1,7 @@
-use cpu::CPU;
+use open_nes::cpu::{CpuError, CPU};
 
-mod cpu;
-
-fn main() {
+fn main() -> Result<(), CpuError> {
     let mut cpu = CPU::new();
     let data = vec![0];
-    cpu.interpret(&data);
+    cpu.load_and_run(&data)
 }
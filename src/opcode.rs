@@ -0,0 +1,870 @@
+#![allow(dead_code, clippy::upper_case_acronyms)]
+
+use std::fmt;
+
+use crate::cpu::Variant;
+
+/// What one opcode byte is on a given chip. The decoder, cycle counts,
+/// disassembler and trace logger all read from `OPCODES` and
+/// `OPCODES_65C02`, so they cannot disagree about an opcode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opcode {
+    pub code: u8,
+    pub mnemonic: Mnemonic,
+    pub mode: AddressingMode,
+    /// Length in bytes, opcode included. BRK counts as one byte even though
+    /// it skips the padding byte after it.
+    pub len: u8,
+    /// Cycles taken before page-crossing and branch penalties. KIL opcodes
+    /// never finish and are listed as 0.
+    pub cycles: u8,
+    /// Whether indexing across a page costs a cycle. For branches, taking
+    /// one to another page does.
+    pub page_cross_penalty: bool,
+    /// Whether the chip's datasheet documents the opcode. The unofficial
+    /// NOPs and SBC at 0xEB run official instructions but are not.
+    pub official: bool,
+    pub class: Class,
+}
+
+impl Opcode {
+    pub fn decode(code: u8, variant: Variant) -> Opcode {
+        match variant {
+            Variant::Nmos6502 | Variant::Ricoh2A03 => OPCODES[code as usize],
+            Variant::Cmos65C02 => OPCODES_65C02[code as usize],
+        }
+    }
+
+    const fn page_cross(self) -> Self {
+        Self {
+            page_cross_penalty: true,
+            ..self
+        }
+    }
+
+    const fn unofficial(self) -> Self {
+        Self {
+            official: false,
+            ..self
+        }
+    }
+}
+
+const fn op(code: u8, mnemonic: Mnemonic, mode: AddressingMode, cycles: u8) -> Opcode {
+    Opcode {
+        code,
+        mnemonic,
+        mode,
+        len: 1 + mode.operand_len(),
+        cycles,
+        page_cross_penalty: false,
+        official: true,
+        class: mnemonic.class(mode),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mnemonic {
+    ADC,
+    ALR,
+    ANC,
+    AND,
+    ARR,
+    ASL,
+    AXS,
+    BCC,
+    BCS,
+    BEQ,
+    BIT,
+    BMI,
+    BNE,
+    BPL,
+    BRA,
+    BRK,
+    BVC,
+    BVS,
+    CLC,
+    CLD,
+    CLI,
+    CLV,
+    CMP,
+    CPX,
+    CPY,
+    DCP,
+    DEC,
+    DEX,
+    DEY,
+    EOR,
+    INC,
+    INX,
+    INY,
+    ISB,
+    JAM,
+    JMP,
+    JSR,
+    LAS,
+    LAX,
+    LDA,
+    LDX,
+    LDY,
+    LSR,
+    LXA,
+    NOP,
+    ORA,
+    PHA,
+    PHP,
+    PHX,
+    PHY,
+    PLA,
+    PLP,
+    PLX,
+    PLY,
+    RLA,
+    ROL,
+    ROR,
+    RRA,
+    RTI,
+    RTS,
+    SAX,
+    SBC,
+    SEC,
+    SED,
+    SEI,
+    SHA,
+    SHX,
+    SHY,
+    SLO,
+    SRE,
+    STA,
+    STX,
+    STY,
+    STZ,
+    TAS,
+    TAX,
+    TAY,
+    TRB,
+    TSB,
+    TSX,
+    TXA,
+    TXS,
+    TYA,
+    XAA,
+}
+
+impl Mnemonic {
+    const fn class(self, mode: AddressingMode) -> Class {
+        use Mnemonic::*;
+
+        match self {
+            ASL | LSR | ROL | ROR | INC | DEC | TRB | TSB | SLO | RLA | SRE | RRA | DCP | ISB => {
+                Class::ReadModifyWrite
+            }
+            STA | STX | STY | STZ | SAX | SHA | SHX | SHY | TAS => Class::Write,
+            BCC | BCS | BEQ | BMI | BNE | BPL | BRA | BVC | BVS | JMP | JSR | RTS | RTI | BRK
+            | JAM | PHA | PHP | PHX | PHY | PLA | PLP | PLX | PLY => Class::Control,
+            _ if matches!(mode, AddressingMode::Implied) => Class::Implied,
+            _ => Class::Read,
+        }
+    }
+
+    /// Unofficial instructions whose results vary between chips.
+    pub fn is_unstable(self) -> bool {
+        use Mnemonic::*;
+
+        matches!(self, XAA | LXA | LAS | SHA | SHX | SHY | TAS)
+    }
+
+    /// Instructions that only unofficial NMOS opcodes run.
+    pub fn is_unofficial(self) -> bool {
+        use Mnemonic::*;
+
+        self.is_unstable()
+            || matches!(
+                self,
+                LAX | SAX | DCP | ISB | SLO | RLA | SRE | RRA | ANC | ALR | ARR | AXS | JAM
+            )
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressingMode {
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+    Accumulator,
+    Implied,
+    /// 65C02 (zp): like (zp),Y without the index.
+    ZeroPageIndirect,
+    /// 65C02 JMP (abs,X).
+    AbsoluteIndirectX,
+}
+
+impl AddressingMode {
+    /// How many operand bytes follow the opcode.
+    pub const fn operand_len(self) -> u8 {
+        use AddressingMode::*;
+
+        match self {
+            Implied | Accumulator => 0,
+            Immediate | ZeroPage | ZeroPageX | ZeroPageY | IndirectX | IndirectY | Relative
+            | ZeroPageIndirect => 1,
+            Absolute | AbsoluteX | AbsoluteY | Indirect | AbsoluteIndirectX => 2,
+        }
+    }
+}
+
+/// How an instruction uses its operand, which decides the shape of its
+/// bus cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    /// Works on registers and flags only.
+    Implied,
+    Read,
+    Write,
+    ReadModifyWrite,
+    /// Branches, jumps, stack instructions, BRK and KIL, each of which has
+    /// a sequence of its own.
+    Control,
+}
+
+/// The NMOS 6502 and 2A03, including the unofficial opcodes.
+#[rustfmt::skip]
+pub static OPCODES: [Opcode; 256] = {
+    use AddressingMode::*;
+    use Mnemonic::*;
+
+    [
+        op(0x00, BRK, Implied, 7),
+        op(0x01, ORA, IndirectX, 6),
+        op(0x02, JAM, Implied, 0).unofficial(),
+        op(0x03, SLO, IndirectX, 8).unofficial(),
+        op(0x04, NOP, ZeroPage, 3).unofficial(),
+        op(0x05, ORA, ZeroPage, 3),
+        op(0x06, ASL, ZeroPage, 5),
+        op(0x07, SLO, ZeroPage, 5).unofficial(),
+        op(0x08, PHP, Implied, 3),
+        op(0x09, ORA, Immediate, 2),
+        op(0x0A, ASL, Accumulator, 2),
+        op(0x0B, ANC, Immediate, 2).unofficial(),
+        op(0x0C, NOP, Absolute, 4).unofficial(),
+        op(0x0D, ORA, Absolute, 4),
+        op(0x0E, ASL, Absolute, 6),
+        op(0x0F, SLO, Absolute, 6).unofficial(),
+        op(0x10, BPL, Relative, 2).page_cross(),
+        op(0x11, ORA, IndirectY, 5).page_cross(),
+        op(0x12, JAM, Implied, 0).unofficial(),
+        op(0x13, SLO, IndirectY, 8).unofficial(),
+        op(0x14, NOP, ZeroPageX, 4).unofficial(),
+        op(0x15, ORA, ZeroPageX, 4),
+        op(0x16, ASL, ZeroPageX, 6),
+        op(0x17, SLO, ZeroPageX, 6).unofficial(),
+        op(0x18, CLC, Implied, 2),
+        op(0x19, ORA, AbsoluteY, 4).page_cross(),
+        op(0x1A, NOP, Implied, 2).unofficial(),
+        op(0x1B, SLO, AbsoluteY, 7).unofficial(),
+        op(0x1C, NOP, AbsoluteX, 4).page_cross().unofficial(),
+        op(0x1D, ORA, AbsoluteX, 4).page_cross(),
+        op(0x1E, ASL, AbsoluteX, 7),
+        op(0x1F, SLO, AbsoluteX, 7).unofficial(),
+        op(0x20, JSR, Absolute, 6),
+        op(0x21, AND, IndirectX, 6),
+        op(0x22, JAM, Implied, 0).unofficial(),
+        op(0x23, RLA, IndirectX, 8).unofficial(),
+        op(0x24, BIT, ZeroPage, 3),
+        op(0x25, AND, ZeroPage, 3),
+        op(0x26, ROL, ZeroPage, 5),
+        op(0x27, RLA, ZeroPage, 5).unofficial(),
+        op(0x28, PLP, Implied, 4),
+        op(0x29, AND, Immediate, 2),
+        op(0x2A, ROL, Accumulator, 2),
+        op(0x2B, ANC, Immediate, 2).unofficial(),
+        op(0x2C, BIT, Absolute, 4),
+        op(0x2D, AND, Absolute, 4),
+        op(0x2E, ROL, Absolute, 6),
+        op(0x2F, RLA, Absolute, 6).unofficial(),
+        op(0x30, BMI, Relative, 2).page_cross(),
+        op(0x31, AND, IndirectY, 5).page_cross(),
+        op(0x32, JAM, Implied, 0).unofficial(),
+        op(0x33, RLA, IndirectY, 8).unofficial(),
+        op(0x34, NOP, ZeroPageX, 4).unofficial(),
+        op(0x35, AND, ZeroPageX, 4),
+        op(0x36, ROL, ZeroPageX, 6),
+        op(0x37, RLA, ZeroPageX, 6).unofficial(),
+        op(0x38, SEC, Implied, 2),
+        op(0x39, AND, AbsoluteY, 4).page_cross(),
+        op(0x3A, NOP, Implied, 2).unofficial(),
+        op(0x3B, RLA, AbsoluteY, 7).unofficial(),
+        op(0x3C, NOP, AbsoluteX, 4).page_cross().unofficial(),
+        op(0x3D, AND, AbsoluteX, 4).page_cross(),
+        op(0x3E, ROL, AbsoluteX, 7),
+        op(0x3F, RLA, AbsoluteX, 7).unofficial(),
+        op(0x40, RTI, Implied, 6),
+        op(0x41, EOR, IndirectX, 6),
+        op(0x42, JAM, Implied, 0).unofficial(),
+        op(0x43, SRE, IndirectX, 8).unofficial(),
+        op(0x44, NOP, ZeroPage, 3).unofficial(),
+        op(0x45, EOR, ZeroPage, 3),
+        op(0x46, LSR, ZeroPage, 5),
+        op(0x47, SRE, ZeroPage, 5).unofficial(),
+        op(0x48, PHA, Implied, 3),
+        op(0x49, EOR, Immediate, 2),
+        op(0x4A, LSR, Accumulator, 2),
+        op(0x4B, ALR, Immediate, 2).unofficial(),
+        op(0x4C, JMP, Absolute, 3),
+        op(0x4D, EOR, Absolute, 4),
+        op(0x4E, LSR, Absolute, 6),
+        op(0x4F, SRE, Absolute, 6).unofficial(),
+        op(0x50, BVC, Relative, 2).page_cross(),
+        op(0x51, EOR, IndirectY, 5).page_cross(),
+        op(0x52, JAM, Implied, 0).unofficial(),
+        op(0x53, SRE, IndirectY, 8).unofficial(),
+        op(0x54, NOP, ZeroPageX, 4).unofficial(),
+        op(0x55, EOR, ZeroPageX, 4),
+        op(0x56, LSR, ZeroPageX, 6),
+        op(0x57, SRE, ZeroPageX, 6).unofficial(),
+        op(0x58, CLI, Implied, 2),
+        op(0x59, EOR, AbsoluteY, 4).page_cross(),
+        op(0x5A, NOP, Implied, 2).unofficial(),
+        op(0x5B, SRE, AbsoluteY, 7).unofficial(),
+        op(0x5C, NOP, AbsoluteX, 4).page_cross().unofficial(),
+        op(0x5D, EOR, AbsoluteX, 4).page_cross(),
+        op(0x5E, LSR, AbsoluteX, 7),
+        op(0x5F, SRE, AbsoluteX, 7).unofficial(),
+        op(0x60, RTS, Implied, 6),
+        op(0x61, ADC, IndirectX, 6),
+        op(0x62, JAM, Implied, 0).unofficial(),
+        op(0x63, RRA, IndirectX, 8).unofficial(),
+        op(0x64, NOP, ZeroPage, 3).unofficial(),
+        op(0x65, ADC, ZeroPage, 3),
+        op(0x66, ROR, ZeroPage, 5),
+        op(0x67, RRA, ZeroPage, 5).unofficial(),
+        op(0x68, PLA, Implied, 4),
+        op(0x69, ADC, Immediate, 2),
+        op(0x6A, ROR, Accumulator, 2),
+        op(0x6B, ARR, Immediate, 2).unofficial(),
+        op(0x6C, JMP, Indirect, 5),
+        op(0x6D, ADC, Absolute, 4),
+        op(0x6E, ROR, Absolute, 6),
+        op(0x6F, RRA, Absolute, 6).unofficial(),
+        op(0x70, BVS, Relative, 2).page_cross(),
+        op(0x71, ADC, IndirectY, 5).page_cross(),
+        op(0x72, JAM, Implied, 0).unofficial(),
+        op(0x73, RRA, IndirectY, 8).unofficial(),
+        op(0x74, NOP, ZeroPageX, 4).unofficial(),
+        op(0x75, ADC, ZeroPageX, 4),
+        op(0x76, ROR, ZeroPageX, 6),
+        op(0x77, RRA, ZeroPageX, 6).unofficial(),
+        op(0x78, SEI, Implied, 2),
+        op(0x79, ADC, AbsoluteY, 4).page_cross(),
+        op(0x7A, NOP, Implied, 2).unofficial(),
+        op(0x7B, RRA, AbsoluteY, 7).unofficial(),
+        op(0x7C, NOP, AbsoluteX, 4).page_cross().unofficial(),
+        op(0x7D, ADC, AbsoluteX, 4).page_cross(),
+        op(0x7E, ROR, AbsoluteX, 7),
+        op(0x7F, RRA, AbsoluteX, 7).unofficial(),
+        op(0x80, NOP, Immediate, 2).unofficial(),
+        op(0x81, STA, IndirectX, 6),
+        op(0x82, NOP, Immediate, 2).unofficial(),
+        op(0x83, SAX, IndirectX, 6).unofficial(),
+        op(0x84, STY, ZeroPage, 3),
+        op(0x85, STA, ZeroPage, 3),
+        op(0x86, STX, ZeroPage, 3),
+        op(0x87, SAX, ZeroPage, 3).unofficial(),
+        op(0x88, DEY, Implied, 2),
+        op(0x89, NOP, Immediate, 2).unofficial(),
+        op(0x8A, TXA, Implied, 2),
+        op(0x8B, XAA, Immediate, 2).unofficial(),
+        op(0x8C, STY, Absolute, 4),
+        op(0x8D, STA, Absolute, 4),
+        op(0x8E, STX, Absolute, 4),
+        op(0x8F, SAX, Absolute, 4).unofficial(),
+        op(0x90, BCC, Relative, 2).page_cross(),
+        op(0x91, STA, IndirectY, 6),
+        op(0x92, JAM, Implied, 0).unofficial(),
+        op(0x93, SHA, IndirectY, 6).unofficial(),
+        op(0x94, STY, ZeroPageX, 4),
+        op(0x95, STA, ZeroPageX, 4),
+        op(0x96, STX, ZeroPageY, 4),
+        op(0x97, SAX, ZeroPageY, 4).unofficial(),
+        op(0x98, TYA, Implied, 2),
+        op(0x99, STA, AbsoluteY, 5),
+        op(0x9A, TXS, Implied, 2),
+        op(0x9B, TAS, AbsoluteY, 5).unofficial(),
+        op(0x9C, SHY, AbsoluteX, 5).unofficial(),
+        op(0x9D, STA, AbsoluteX, 5),
+        op(0x9E, SHX, AbsoluteY, 5).unofficial(),
+        op(0x9F, SHA, AbsoluteY, 5).unofficial(),
+        op(0xA0, LDY, Immediate, 2),
+        op(0xA1, LDA, IndirectX, 6),
+        op(0xA2, LDX, Immediate, 2),
+        op(0xA3, LAX, IndirectX, 6).unofficial(),
+        op(0xA4, LDY, ZeroPage, 3),
+        op(0xA5, LDA, ZeroPage, 3),
+        op(0xA6, LDX, ZeroPage, 3),
+        op(0xA7, LAX, ZeroPage, 3).unofficial(),
+        op(0xA8, TAY, Implied, 2),
+        op(0xA9, LDA, Immediate, 2),
+        op(0xAA, TAX, Implied, 2),
+        op(0xAB, LXA, Immediate, 2).unofficial(),
+        op(0xAC, LDY, Absolute, 4),
+        op(0xAD, LDA, Absolute, 4),
+        op(0xAE, LDX, Absolute, 4),
+        op(0xAF, LAX, Absolute, 4).unofficial(),
+        op(0xB0, BCS, Relative, 2).page_cross(),
+        op(0xB1, LDA, IndirectY, 5).page_cross(),
+        op(0xB2, JAM, Implied, 0).unofficial(),
+        op(0xB3, LAX, IndirectY, 5).page_cross().unofficial(),
+        op(0xB4, LDY, ZeroPageX, 4),
+        op(0xB5, LDA, ZeroPageX, 4),
+        op(0xB6, LDX, ZeroPageY, 4),
+        op(0xB7, LAX, ZeroPageY, 4).unofficial(),
+        op(0xB8, CLV, Implied, 2),
+        op(0xB9, LDA, AbsoluteY, 4).page_cross(),
+        op(0xBA, TSX, Implied, 2),
+        op(0xBB, LAS, AbsoluteY, 4).page_cross().unofficial(),
+        op(0xBC, LDY, AbsoluteX, 4).page_cross(),
+        op(0xBD, LDA, AbsoluteX, 4).page_cross(),
+        op(0xBE, LDX, AbsoluteY, 4).page_cross(),
+        op(0xBF, LAX, AbsoluteY, 4).page_cross().unofficial(),
+        op(0xC0, CPY, Immediate, 2),
+        op(0xC1, CMP, IndirectX, 6),
+        op(0xC2, NOP, Immediate, 2).unofficial(),
+        op(0xC3, DCP, IndirectX, 8).unofficial(),
+        op(0xC4, CPY, ZeroPage, 3),
+        op(0xC5, CMP, ZeroPage, 3),
+        op(0xC6, DEC, ZeroPage, 5),
+        op(0xC7, DCP, ZeroPage, 5).unofficial(),
+        op(0xC8, INY, Implied, 2),
+        op(0xC9, CMP, Immediate, 2),
+        op(0xCA, DEX, Implied, 2),
+        op(0xCB, AXS, Immediate, 2).unofficial(),
+        op(0xCC, CPY, Absolute, 4),
+        op(0xCD, CMP, Absolute, 4),
+        op(0xCE, DEC, Absolute, 6),
+        op(0xCF, DCP, Absolute, 6).unofficial(),
+        op(0xD0, BNE, Relative, 2).page_cross(),
+        op(0xD1, CMP, IndirectY, 5).page_cross(),
+        op(0xD2, JAM, Implied, 0).unofficial(),
+        op(0xD3, DCP, IndirectY, 8).unofficial(),
+        op(0xD4, NOP, ZeroPageX, 4).unofficial(),
+        op(0xD5, CMP, ZeroPageX, 4),
+        op(0xD6, DEC, ZeroPageX, 6),
+        op(0xD7, DCP, ZeroPageX, 6).unofficial(),
+        op(0xD8, CLD, Implied, 2),
+        op(0xD9, CMP, AbsoluteY, 4).page_cross(),
+        op(0xDA, NOP, Implied, 2).unofficial(),
+        op(0xDB, DCP, AbsoluteY, 7).unofficial(),
+        op(0xDC, NOP, AbsoluteX, 4).page_cross().unofficial(),
+        op(0xDD, CMP, AbsoluteX, 4).page_cross(),
+        op(0xDE, DEC, AbsoluteX, 7),
+        op(0xDF, DCP, AbsoluteX, 7).unofficial(),
+        op(0xE0, CPX, Immediate, 2),
+        op(0xE1, SBC, IndirectX, 6),
+        op(0xE2, NOP, Immediate, 2).unofficial(),
+        op(0xE3, ISB, IndirectX, 8).unofficial(),
+        op(0xE4, CPX, ZeroPage, 3),
+        op(0xE5, SBC, ZeroPage, 3),
+        op(0xE6, INC, ZeroPage, 5),
+        op(0xE7, ISB, ZeroPage, 5).unofficial(),
+        op(0xE8, INX, Implied, 2),
+        op(0xE9, SBC, Immediate, 2),
+        op(0xEA, NOP, Implied, 2),
+        op(0xEB, SBC, Immediate, 2).unofficial(),
+        op(0xEC, CPX, Absolute, 4),
+        op(0xED, SBC, Absolute, 4),
+        op(0xEE, INC, Absolute, 6),
+        op(0xEF, ISB, Absolute, 6).unofficial(),
+        op(0xF0, BEQ, Relative, 2).page_cross(),
+        op(0xF1, SBC, IndirectY, 5).page_cross(),
+        op(0xF2, JAM, Implied, 0).unofficial(),
+        op(0xF3, ISB, IndirectY, 8).unofficial(),
+        op(0xF4, NOP, ZeroPageX, 4).unofficial(),
+        op(0xF5, SBC, ZeroPageX, 4),
+        op(0xF6, INC, ZeroPageX, 6),
+        op(0xF7, ISB, ZeroPageX, 6).unofficial(),
+        op(0xF8, SED, Implied, 2),
+        op(0xF9, SBC, AbsoluteY, 4).page_cross(),
+        op(0xFA, NOP, Implied, 2).unofficial(),
+        op(0xFB, ISB, AbsoluteY, 7).unofficial(),
+        op(0xFC, NOP, AbsoluteX, 4).page_cross().unofficial(),
+        op(0xFD, SBC, AbsoluteX, 4).page_cross(),
+        op(0xFE, INC, AbsoluteX, 7),
+        op(0xFF, ISB, AbsoluteX, 7).unofficial(),
+    ]
+};
+
+/// The 65C02. It keeps every official NMOS opcode, reuses the KIL and
+/// unofficial slots for its additions and runs the rest as NOPs.
+#[rustfmt::skip]
+pub static OPCODES_65C02: [Opcode; 256] = {
+    use AddressingMode::*;
+    use Mnemonic::*;
+
+    [
+        op(0x00, BRK, Implied, 7),
+        op(0x01, ORA, IndirectX, 6),
+        op(0x02, NOP, Immediate, 2).unofficial(),
+        op(0x03, NOP, Implied, 1).unofficial(),
+        op(0x04, TSB, ZeroPage, 5),
+        op(0x05, ORA, ZeroPage, 3),
+        op(0x06, ASL, ZeroPage, 5),
+        op(0x07, NOP, Implied, 1).unofficial(),
+        op(0x08, PHP, Implied, 3),
+        op(0x09, ORA, Immediate, 2),
+        op(0x0A, ASL, Accumulator, 2),
+        op(0x0B, NOP, Implied, 1).unofficial(),
+        op(0x0C, TSB, Absolute, 6),
+        op(0x0D, ORA, Absolute, 4),
+        op(0x0E, ASL, Absolute, 6),
+        op(0x0F, NOP, Implied, 1).unofficial(),
+        op(0x10, BPL, Relative, 2).page_cross(),
+        op(0x11, ORA, IndirectY, 5).page_cross(),
+        op(0x12, ORA, ZeroPageIndirect, 5),
+        op(0x13, NOP, Implied, 1).unofficial(),
+        op(0x14, TRB, ZeroPage, 5),
+        op(0x15, ORA, ZeroPageX, 4),
+        op(0x16, ASL, ZeroPageX, 6),
+        op(0x17, NOP, Implied, 1).unofficial(),
+        op(0x18, CLC, Implied, 2),
+        op(0x19, ORA, AbsoluteY, 4).page_cross(),
+        op(0x1A, INC, Accumulator, 2),
+        op(0x1B, NOP, Implied, 1).unofficial(),
+        op(0x1C, TRB, Absolute, 6),
+        op(0x1D, ORA, AbsoluteX, 4).page_cross(),
+        op(0x1E, ASL, AbsoluteX, 6).page_cross(),
+        op(0x1F, NOP, Implied, 1).unofficial(),
+        op(0x20, JSR, Absolute, 6),
+        op(0x21, AND, IndirectX, 6),
+        op(0x22, NOP, Immediate, 2).unofficial(),
+        op(0x23, NOP, Implied, 1).unofficial(),
+        op(0x24, BIT, ZeroPage, 3),
+        op(0x25, AND, ZeroPage, 3),
+        op(0x26, ROL, ZeroPage, 5),
+        op(0x27, NOP, Implied, 1).unofficial(),
+        op(0x28, PLP, Implied, 4),
+        op(0x29, AND, Immediate, 2),
+        op(0x2A, ROL, Accumulator, 2),
+        op(0x2B, NOP, Implied, 1).unofficial(),
+        op(0x2C, BIT, Absolute, 4),
+        op(0x2D, AND, Absolute, 4),
+        op(0x2E, ROL, Absolute, 6),
+        op(0x2F, NOP, Implied, 1).unofficial(),
+        op(0x30, BMI, Relative, 2).page_cross(),
+        op(0x31, AND, IndirectY, 5).page_cross(),
+        op(0x32, AND, ZeroPageIndirect, 5),
+        op(0x33, NOP, Implied, 1).unofficial(),
+        op(0x34, BIT, ZeroPageX, 4),
+        op(0x35, AND, ZeroPageX, 4),
+        op(0x36, ROL, ZeroPageX, 6),
+        op(0x37, NOP, Implied, 1).unofficial(),
+        op(0x38, SEC, Implied, 2),
+        op(0x39, AND, AbsoluteY, 4).page_cross(),
+        op(0x3A, DEC, Accumulator, 2),
+        op(0x3B, NOP, Implied, 1).unofficial(),
+        op(0x3C, BIT, AbsoluteX, 4).page_cross(),
+        op(0x3D, AND, AbsoluteX, 4).page_cross(),
+        op(0x3E, ROL, AbsoluteX, 6).page_cross(),
+        op(0x3F, NOP, Implied, 1).unofficial(),
+        op(0x40, RTI, Implied, 6),
+        op(0x41, EOR, IndirectX, 6),
+        op(0x42, NOP, Immediate, 2).unofficial(),
+        op(0x43, NOP, Implied, 1).unofficial(),
+        op(0x44, NOP, ZeroPage, 3).unofficial(),
+        op(0x45, EOR, ZeroPage, 3),
+        op(0x46, LSR, ZeroPage, 5),
+        op(0x47, NOP, Implied, 1).unofficial(),
+        op(0x48, PHA, Implied, 3),
+        op(0x49, EOR, Immediate, 2),
+        op(0x4A, LSR, Accumulator, 2),
+        op(0x4B, NOP, Implied, 1).unofficial(),
+        op(0x4C, JMP, Absolute, 3),
+        op(0x4D, EOR, Absolute, 4),
+        op(0x4E, LSR, Absolute, 6),
+        op(0x4F, NOP, Implied, 1).unofficial(),
+        op(0x50, BVC, Relative, 2).page_cross(),
+        op(0x51, EOR, IndirectY, 5).page_cross(),
+        op(0x52, EOR, ZeroPageIndirect, 5),
+        op(0x53, NOP, Implied, 1).unofficial(),
+        op(0x54, NOP, ZeroPageX, 4).unofficial(),
+        op(0x55, EOR, ZeroPageX, 4),
+        op(0x56, LSR, ZeroPageX, 6),
+        op(0x57, NOP, Implied, 1).unofficial(),
+        op(0x58, CLI, Implied, 2),
+        op(0x59, EOR, AbsoluteY, 4).page_cross(),
+        op(0x5A, PHY, Implied, 3),
+        op(0x5B, NOP, Implied, 1).unofficial(),
+        op(0x5C, NOP, Absolute, 8).unofficial(),
+        op(0x5D, EOR, AbsoluteX, 4).page_cross(),
+        op(0x5E, LSR, AbsoluteX, 6).page_cross(),
+        op(0x5F, NOP, Implied, 1).unofficial(),
+        op(0x60, RTS, Implied, 6),
+        op(0x61, ADC, IndirectX, 6),
+        op(0x62, NOP, Immediate, 2).unofficial(),
+        op(0x63, NOP, Implied, 1).unofficial(),
+        op(0x64, STZ, ZeroPage, 3),
+        op(0x65, ADC, ZeroPage, 3),
+        op(0x66, ROR, ZeroPage, 5),
+        op(0x67, NOP, Implied, 1).unofficial(),
+        op(0x68, PLA, Implied, 4),
+        op(0x69, ADC, Immediate, 2),
+        op(0x6A, ROR, Accumulator, 2),
+        op(0x6B, NOP, Implied, 1).unofficial(),
+        op(0x6C, JMP, Indirect, 6),
+        op(0x6D, ADC, Absolute, 4),
+        op(0x6E, ROR, Absolute, 6),
+        op(0x6F, NOP, Implied, 1).unofficial(),
+        op(0x70, BVS, Relative, 2).page_cross(),
+        op(0x71, ADC, IndirectY, 5).page_cross(),
+        op(0x72, ADC, ZeroPageIndirect, 5),
+        op(0x73, NOP, Implied, 1).unofficial(),
+        op(0x74, STZ, ZeroPageX, 4),
+        op(0x75, ADC, ZeroPageX, 4),
+        op(0x76, ROR, ZeroPageX, 6),
+        op(0x77, NOP, Implied, 1).unofficial(),
+        op(0x78, SEI, Implied, 2),
+        op(0x79, ADC, AbsoluteY, 4).page_cross(),
+        op(0x7A, PLY, Implied, 4),
+        op(0x7B, NOP, Implied, 1).unofficial(),
+        op(0x7C, JMP, AbsoluteIndirectX, 6),
+        op(0x7D, ADC, AbsoluteX, 4).page_cross(),
+        op(0x7E, ROR, AbsoluteX, 6).page_cross(),
+        op(0x7F, NOP, Implied, 1).unofficial(),
+        op(0x80, BRA, Relative, 2).page_cross(),
+        op(0x81, STA, IndirectX, 6),
+        op(0x82, NOP, Immediate, 2).unofficial(),
+        op(0x83, NOP, Implied, 1).unofficial(),
+        op(0x84, STY, ZeroPage, 3),
+        op(0x85, STA, ZeroPage, 3),
+        op(0x86, STX, ZeroPage, 3),
+        op(0x87, NOP, Implied, 1).unofficial(),
+        op(0x88, DEY, Implied, 2),
+        op(0x89, BIT, Immediate, 2),
+        op(0x8A, TXA, Implied, 2),
+        op(0x8B, NOP, Implied, 1).unofficial(),
+        op(0x8C, STY, Absolute, 4),
+        op(0x8D, STA, Absolute, 4),
+        op(0x8E, STX, Absolute, 4),
+        op(0x8F, NOP, Implied, 1).unofficial(),
+        op(0x90, BCC, Relative, 2).page_cross(),
+        op(0x91, STA, IndirectY, 6),
+        op(0x92, STA, ZeroPageIndirect, 5),
+        op(0x93, NOP, Implied, 1).unofficial(),
+        op(0x94, STY, ZeroPageX, 4),
+        op(0x95, STA, ZeroPageX, 4),
+        op(0x96, STX, ZeroPageY, 4),
+        op(0x97, NOP, Implied, 1).unofficial(),
+        op(0x98, TYA, Implied, 2),
+        op(0x99, STA, AbsoluteY, 5),
+        op(0x9A, TXS, Implied, 2),
+        op(0x9B, NOP, Implied, 1).unofficial(),
+        op(0x9C, STZ, Absolute, 4),
+        op(0x9D, STA, AbsoluteX, 5),
+        op(0x9E, STZ, AbsoluteX, 5),
+        op(0x9F, NOP, Implied, 1).unofficial(),
+        op(0xA0, LDY, Immediate, 2),
+        op(0xA1, LDA, IndirectX, 6),
+        op(0xA2, LDX, Immediate, 2),
+        op(0xA3, NOP, Implied, 1).unofficial(),
+        op(0xA4, LDY, ZeroPage, 3),
+        op(0xA5, LDA, ZeroPage, 3),
+        op(0xA6, LDX, ZeroPage, 3),
+        op(0xA7, NOP, Implied, 1).unofficial(),
+        op(0xA8, TAY, Implied, 2),
+        op(0xA9, LDA, Immediate, 2),
+        op(0xAA, TAX, Implied, 2),
+        op(0xAB, NOP, Implied, 1).unofficial(),
+        op(0xAC, LDY, Absolute, 4),
+        op(0xAD, LDA, Absolute, 4),
+        op(0xAE, LDX, Absolute, 4),
+        op(0xAF, NOP, Implied, 1).unofficial(),
+        op(0xB0, BCS, Relative, 2).page_cross(),
+        op(0xB1, LDA, IndirectY, 5).page_cross(),
+        op(0xB2, LDA, ZeroPageIndirect, 5),
+        op(0xB3, NOP, Implied, 1).unofficial(),
+        op(0xB4, LDY, ZeroPageX, 4),
+        op(0xB5, LDA, ZeroPageX, 4),
+        op(0xB6, LDX, ZeroPageY, 4),
+        op(0xB7, NOP, Implied, 1).unofficial(),
+        op(0xB8, CLV, Implied, 2),
+        op(0xB9, LDA, AbsoluteY, 4).page_cross(),
+        op(0xBA, TSX, Implied, 2),
+        op(0xBB, NOP, Implied, 1).unofficial(),
+        op(0xBC, LDY, AbsoluteX, 4).page_cross(),
+        op(0xBD, LDA, AbsoluteX, 4).page_cross(),
+        op(0xBE, LDX, AbsoluteY, 4).page_cross(),
+        op(0xBF, NOP, Implied, 1).unofficial(),
+        op(0xC0, CPY, Immediate, 2),
+        op(0xC1, CMP, IndirectX, 6),
+        op(0xC2, NOP, Immediate, 2).unofficial(),
+        op(0xC3, NOP, Implied, 1).unofficial(),
+        op(0xC4, CPY, ZeroPage, 3),
+        op(0xC5, CMP, ZeroPage, 3),
+        op(0xC6, DEC, ZeroPage, 5),
+        op(0xC7, NOP, Implied, 1).unofficial(),
+        op(0xC8, INY, Implied, 2),
+        op(0xC9, CMP, Immediate, 2),
+        op(0xCA, DEX, Implied, 2),
+        op(0xCB, NOP, Implied, 1).unofficial(),
+        op(0xCC, CPY, Absolute, 4),
+        op(0xCD, CMP, Absolute, 4),
+        op(0xCE, DEC, Absolute, 6),
+        op(0xCF, NOP, Implied, 1).unofficial(),
+        op(0xD0, BNE, Relative, 2).page_cross(),
+        op(0xD1, CMP, IndirectY, 5).page_cross(),
+        op(0xD2, CMP, ZeroPageIndirect, 5),
+        op(0xD3, NOP, Implied, 1).unofficial(),
+        op(0xD4, NOP, ZeroPageX, 4).unofficial(),
+        op(0xD5, CMP, ZeroPageX, 4),
+        op(0xD6, DEC, ZeroPageX, 6),
+        op(0xD7, NOP, Implied, 1).unofficial(),
+        op(0xD8, CLD, Implied, 2),
+        op(0xD9, CMP, AbsoluteY, 4).page_cross(),
+        op(0xDA, PHX, Implied, 3),
+        op(0xDB, NOP, Implied, 1).unofficial(),
+        op(0xDC, NOP, Absolute, 4).unofficial(),
+        op(0xDD, CMP, AbsoluteX, 4).page_cross(),
+        op(0xDE, DEC, AbsoluteX, 7),
+        op(0xDF, NOP, Implied, 1).unofficial(),
+        op(0xE0, CPX, Immediate, 2),
+        op(0xE1, SBC, IndirectX, 6),
+        op(0xE2, NOP, Immediate, 2).unofficial(),
+        op(0xE3, NOP, Implied, 1).unofficial(),
+        op(0xE4, CPX, ZeroPage, 3),
+        op(0xE5, SBC, ZeroPage, 3),
+        op(0xE6, INC, ZeroPage, 5),
+        op(0xE7, NOP, Implied, 1).unofficial(),
+        op(0xE8, INX, Implied, 2),
+        op(0xE9, SBC, Immediate, 2),
+        op(0xEA, NOP, Implied, 2),
+        op(0xEB, NOP, Implied, 1).unofficial(),
+        op(0xEC, CPX, Absolute, 4),
+        op(0xED, SBC, Absolute, 4),
+        op(0xEE, INC, Absolute, 6),
+        op(0xEF, NOP, Implied, 1).unofficial(),
+        op(0xF0, BEQ, Relative, 2).page_cross(),
+        op(0xF1, SBC, IndirectY, 5).page_cross(),
+        op(0xF2, SBC, ZeroPageIndirect, 5),
+        op(0xF3, NOP, Implied, 1).unofficial(),
+        op(0xF4, NOP, ZeroPageX, 4).unofficial(),
+        op(0xF5, SBC, ZeroPageX, 4),
+        op(0xF6, INC, ZeroPageX, 6),
+        op(0xF7, NOP, Implied, 1).unofficial(),
+        op(0xF8, SED, Implied, 2),
+        op(0xF9, SBC, AbsoluteY, 4).page_cross(),
+        op(0xFA, PLX, Implied, 4),
+        op(0xFB, NOP, Implied, 1).unofficial(),
+        op(0xFC, NOP, Absolute, 4).unofficial(),
+        op(0xFD, SBC, AbsoluteX, 4).page_cross(),
+        op(0xFE, INC, AbsoluteX, 7),
+        op(0xFF, NOP, Implied, 1).unofficial(),
+    ]
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_tables_are_indexed_by_opcode() {
+        for code in 0..=0xff {
+            assert_eq!(OPCODES[code as usize].code, code);
+            assert_eq!(OPCODES_65C02[code as usize].code, code);
+        }
+    }
+
+    #[test]
+    fn test_official_opcode_counts() {
+        assert_eq!(OPCODES.iter().filter(|op| op.official).count(), 151);
+        assert_eq!(OPCODES_65C02.iter().filter(|op| op.official).count(), 178);
+    }
+
+    #[test]
+    fn test_decode_reads_the_variant_table() {
+        let lda = Opcode::decode(0xBD, Variant::Ricoh2A03);
+        assert_eq!(lda.mnemonic, Mnemonic::LDA);
+        assert_eq!(lda.mode, AddressingMode::AbsoluteX);
+        assert_eq!(lda.len, 3);
+        assert_eq!(lda.cycles, 4);
+        assert_eq!(lda.page_cross_penalty, true);
+        assert_eq!(lda.official, true);
+        assert_eq!(lda.class, Class::Read);
+
+        assert_eq!(
+            Opcode::decode(0x80, Variant::Nmos6502).mnemonic,
+            Mnemonic::NOP
+        );
+        assert_eq!(
+            Opcode::decode(0x80, Variant::Cmos65C02).mnemonic,
+            Mnemonic::BRA
+        );
+    }
+
+    #[test]
+    fn test_unofficial_nops_and_sbc_are_not_official() {
+        let sbc = Opcode::decode(0xEB, Variant::Nmos6502);
+        assert_eq!((sbc.mnemonic, sbc.official), (Mnemonic::SBC, false));
+        let nop = Opcode::decode(0x1A, Variant::Nmos6502);
+        assert_eq!((nop.mnemonic, nop.official), (Mnemonic::NOP, false));
+        let nop = Opcode::decode(0xEA, Variant::Nmos6502);
+        assert_eq!((nop.mnemonic, nop.official), (Mnemonic::NOP, true));
+    }
+
+    #[test]
+    fn test_nmos_page_cross_penalty_is_for_indexed_reads_and_branches() {
+        for op in OPCODES.iter() {
+            let indexed = matches!(
+                op.mode,
+                AddressingMode::AbsoluteX | AddressingMode::AbsoluteY | AddressingMode::IndirectY
+            );
+            assert_eq!(
+                op.page_cross_penalty,
+                (op.class == Class::Read && indexed) || op.mode == AddressingMode::Relative,
+                "0x{:02X}",
+                op.code
+            );
+        }
+    }
+
+    #[test]
+    fn test_classes() {
+        assert_eq!(
+            Opcode::decode(0xEA, Variant::Nmos6502).class,
+            Class::Implied
+        );
+        assert_eq!(Opcode::decode(0x04, Variant::Nmos6502).class, Class::Read);
+        assert_eq!(Opcode::decode(0x9D, Variant::Nmos6502).class, Class::Write);
+        assert_eq!(
+            Opcode::decode(0x0A, Variant::Nmos6502).class,
+            Class::ReadModifyWrite
+        );
+        assert_eq!(
+            Opcode::decode(0xC7, Variant::Nmos6502).class,
+            Class::ReadModifyWrite
+        );
+        assert_eq!(
+            Opcode::decode(0x48, Variant::Nmos6502).class,
+            Class::Control
+        );
+    }
+
+    #[test]
+    fn test_mnemonic_display() {
+        assert_eq!(Mnemonic::ADC.to_string(), "ADC");
+        assert_eq!(Mnemonic::ISB.to_string(), "ISB");
+    }
+}
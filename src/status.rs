@@ -0,0 +1,79 @@
+use std::fmt;
+
+use bitflags::bitflags;
+
+bitflags! {
+    /// The 6502 processor status register `P`.
+    ///
+    /// `BREAK` and `UNUSED` are not real latches: they only show up in the
+    /// copy of `P` pushed to the stack, and `UNUSED` always reads back as 1.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Status: u8 {
+        const CARRY = 0b0000_0001;
+        const ZERO = 0b0000_0010;
+        const INTERRUPT_DISABLE = 0b0000_0100;
+        const DECIMAL = 0b0000_1000;
+        const BREAK = 0b0001_0000;
+        const UNUSED = 0b0010_0000;
+        const OVERFLOW = 0b0100_0000;
+        const NEGATIVE = 0b1000_0000;
+    }
+}
+
+impl fmt::Display for Status {
+    /// Formats the flags in `NV-BDIZC` order, upper case when set and lower
+    /// case when clear, e.g. `nV-bdIzC`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = [
+            (Status::NEGATIVE, 'N'),
+            (Status::OVERFLOW, 'V'),
+            (Status::UNUSED, '-'),
+            (Status::BREAK, 'B'),
+            (Status::DECIMAL, 'D'),
+            (Status::INTERRUPT_DISABLE, 'I'),
+            (Status::ZERO, 'Z'),
+            (Status::CARRY, 'C'),
+        ];
+        for (flag, name) in flags {
+            let name = if self.contains(flag) {
+                name
+            } else {
+                name.to_ascii_lowercase()
+            };
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_display_all_clear() {
+        assert_eq!(Status::empty().to_string(), "nv-bdizc");
+    }
+
+    #[test]
+    fn test_display_all_set() {
+        assert_eq!(Status::all().to_string(), "NV-BDIZC");
+    }
+
+    #[test]
+    fn test_display_mixed() {
+        let status = Status::OVERFLOW | Status::UNUSED | Status::INTERRUPT_DISABLE | Status::CARRY;
+
+        assert_eq!(status.to_string(), "nV-bdIzC");
+    }
+
+    #[test]
+    fn test_bits_match_hardware_layout() {
+        assert_eq!(
+            Status::from_bits_truncate(0x24),
+            Status::UNUSED | Status::INTERRUPT_DISABLE
+        );
+        assert_eq!((Status::NEGATIVE | Status::CARRY).bits(), 0x81);
+    }
+}
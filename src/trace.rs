@@ -0,0 +1,235 @@
+use crate::bus::Bus;
+use crate::cpu::{Variant, CPU};
+use crate::disasm::Instruction;
+use crate::opcode::{AddressingMode, Mnemonic};
+
+/// Dots per scanline and scanlines per frame of the NTSC PPU.
+const DOTS_PER_SCANLINE: u64 = 341;
+const SCANLINES_PER_FRAME: u64 = 262;
+
+/// Receives the trace line of each instruction `CPU` runs.
+pub type Tracer = Box<dyn FnMut(&str)>;
+
+/// Formats the instruction at PC and the CPU state before it runs, as a
+/// line of nestest.log:
+///
+/// ```text
+/// C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7
+/// ```
+///
+/// The instruction is disassembled as `Instruction` shows it, followed
+/// by the effective address and the value currently there. Memory is only
+/// peeked, so tracing has no side effects. There is no PPU yet, so its
+/// position is worked out from the cycle count, at three dots per cycle
+/// from scanline 0, dot 0.
+pub fn trace<B: Bus>(cpu: &CPU<B>) -> String {
+    let pc = cpu.program_counter;
+    let bytes: Vec<u8> = (0..3)
+        .map(|offset| cpu.bus.peek(pc.wrapping_add(offset)))
+        .collect();
+    let instruction =
+        Instruction::decode(&bytes, pc, cpu.variant).expect("three bytes were peeked");
+    let listing = format!("{}{}", instruction, resolved(cpu, &instruction));
+
+    let dots = cpu.cycles * 3;
+    format!(
+        "{:<47} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        listing,
+        cpu.register_a,
+        cpu.register_x,
+        cpu.register_y,
+        cpu.status.bits(),
+        cpu.stack_pointer,
+        dots / DOTS_PER_SCANLINE % SCANLINES_PER_FRAME,
+        dots % DOTS_PER_SCANLINE,
+        cpu.cycles
+    )
+}
+
+/// What nestest.log shows after the operand: the address it resolves to
+/// and the value there.
+fn resolved<B: Bus>(cpu: &CPU<B>, instruction: &Instruction) -> String {
+    let bus = &cpu.bus;
+    let Some(operand) = instruction.operand() else {
+        return String::new();
+    };
+
+    match instruction.opcode.mode {
+        AddressingMode::Implied
+        | AddressingMode::Accumulator
+        | AddressingMode::Immediate
+        | AddressingMode::Relative => String::new(),
+        AddressingMode::ZeroPage => format!(" = {:02X}", bus.peek(operand)),
+        AddressingMode::ZeroPageX | AddressingMode::ZeroPageY => {
+            let index = index_register(cpu, instruction.opcode.mode);
+            let addr = (operand as u8).wrapping_add(index);
+            format!(" @ {:02X} = {:02X}", addr, bus.peek(addr as u16))
+        }
+        AddressingMode::Absolute => match instruction.opcode.mnemonic {
+            Mnemonic::JMP | Mnemonic::JSR => String::new(),
+            _ => format!(" = {:02X}", bus.peek(operand)),
+        },
+        AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => {
+            let index = index_register(cpu, instruction.opcode.mode);
+            let addr = operand.wrapping_add(index as u16);
+            format!(" @ {:04X} = {:02X}", addr, bus.peek(addr))
+        }
+        AddressingMode::Indirect => {
+            // The NMOS parts do not carry into the high byte of the pointer.
+            let target = match cpu.variant {
+                Variant::Cmos65C02 => peek_u16(bus, operand),
+                _ => {
+                    let hi = (operand & 0xFF00) | (operand.wrapping_add(1) & 0x00FF);
+                    u16::from_le_bytes([bus.peek(operand), bus.peek(hi)])
+                }
+            };
+            format!(" = {:04X}", target)
+        }
+        AddressingMode::IndirectX => {
+            let pointer = (operand as u8).wrapping_add(cpu.register_x);
+            let addr = peek_u16_zero_page(bus, pointer);
+            format!(" @ {:02X} = {:04X} = {:02X}", pointer, addr, bus.peek(addr))
+        }
+        AddressingMode::IndirectY => {
+            let base = peek_u16_zero_page(bus, operand as u8);
+            let addr = base.wrapping_add(cpu.register_y as u16);
+            format!(" = {:04X} @ {:04X} = {:02X}", base, addr, bus.peek(addr))
+        }
+        AddressingMode::ZeroPageIndirect => {
+            let addr = peek_u16_zero_page(bus, operand as u8);
+            format!(" = {:04X} = {:02X}", addr, bus.peek(addr))
+        }
+        AddressingMode::AbsoluteIndirectX => {
+            let pointer = operand.wrapping_add(cpu.register_x as u16);
+            format!(" = {:04X}", peek_u16(bus, pointer))
+        }
+    }
+}
+
+fn index_register<B: Bus>(cpu: &CPU<B>, mode: AddressingMode) -> u8 {
+    match mode {
+        AddressingMode::ZeroPageY | AddressingMode::AbsoluteY => cpu.register_y,
+        _ => cpu.register_x,
+    }
+}
+
+fn peek_u16<B: Bus>(bus: &B, addr: u16) -> u16 {
+    u16::from_le_bytes([bus.peek(addr), bus.peek(addr.wrapping_add(1))])
+}
+
+/// Reads a pointer from the zero page, wrapping from 0xFF to 0x00.
+fn peek_u16_zero_page<B: Bus>(bus: &B, addr: u8) -> u16 {
+    u16::from_le_bytes([bus.peek(addr as u16), bus.peek(addr.wrapping_add(1) as u16)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::Ram;
+    use crate::status::Status;
+    use pretty_assertions::assert_eq;
+
+    fn cpu_with_program(program: &[u8]) -> CPU<Ram> {
+        let mut cpu = CPU::new();
+        cpu.load(program, 0xC000).unwrap();
+        cpu.program_counter = 0xC000;
+        cpu.status = Status::from_bits_truncate(0x24);
+        cpu.cycles = 7;
+        cpu
+    }
+
+    #[test]
+    fn test_trace_matches_first_nestest_line() {
+        let cpu = cpu_with_program(&[0x4C, 0xF5, 0xC5]);
+
+        assert_eq!(
+            trace(&cpu),
+            "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7"
+        );
+    }
+
+    #[test]
+    fn test_trace_resolves_indexed_operand() {
+        let mut cpu = cpu_with_program(&[0xBD, 0x00, 0x02]);
+        cpu.register_x = 0x05;
+        cpu.mem_write(0x0205, 0x3F);
+
+        assert!(trace(&cpu).starts_with("C000  BD 00 02  LDA $0200,X @ 0205 = 3F "));
+    }
+
+    #[test]
+    fn test_trace_marks_unofficial_opcodes() {
+        let mut cpu = cpu_with_program(&[0x04, 0xA9]);
+        cpu.mem_write(0x00A9, 0x12);
+
+        assert!(trace(&cpu).starts_with("C000  04 A9    *NOP $A9 = 12 "));
+    }
+
+    #[test]
+    fn test_trace_resolves_indirect_operands() {
+        let mut cpu = cpu_with_program(&[0xA1, 0x80]);
+        cpu.mem_write_u16(0x0080, 0x0200);
+        cpu.mem_write(0x0200, 0x5A);
+        assert!(trace(&cpu).contains("LDA ($80,X) @ 80 = 0200 = 5A "));
+
+        let mut cpu = cpu_with_program(&[0xB1, 0xFF]);
+        cpu.register_y = 0x34;
+        cpu.mem_write(0x00FF, 0x00);
+        cpu.mem_write(0x0000, 0x03);
+        cpu.mem_write(0x0334, 0x89);
+        assert!(trace(&cpu).contains("LDA ($FF),Y = 0300 @ 0334 = 89 "));
+    }
+
+    #[test]
+    fn test_trace_shows_nmos_jmp_indirect_page_wrap() {
+        let mut cpu = cpu_with_program(&[0x6C, 0xFF, 0x02]);
+        cpu.mem_write(0x02FF, 0x00);
+        cpu.mem_write(0x0200, 0xDB);
+        cpu.mem_write(0x0300, 0x12);
+
+        assert!(trace(&cpu).contains("JMP ($02FF) = DB00 "));
+    }
+
+    #[test]
+    fn test_trace_shows_branch_target_and_accumulator() {
+        let cpu = cpu_with_program(&[0xB0, 0xFC]);
+        assert!(trace(&cpu).starts_with("C000  B0 FC     BCS $BFFE "));
+
+        let cpu = cpu_with_program(&[0x4A]);
+        assert!(trace(&cpu).starts_with("C000  4A        LSR A "));
+    }
+
+    #[test]
+    fn test_trace_ppu_position_follows_cycles() {
+        let mut cpu = cpu_with_program(&[0xEA]);
+        cpu.cycles = 27_384;
+        assert!(trace(&cpu).ends_with("PPU:240,312 CYC:27384"));
+
+        cpu.cycles = 29_781;
+        assert!(trace(&cpu).ends_with("PPU:  0,  1 CYC:29781"));
+    }
+
+    #[test]
+    fn test_cpu_sends_a_trace_line_per_instruction() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let mut cpu = cpu_with_program(&[0xE8, 0xE8]);
+        let sink = Rc::clone(&lines);
+        cpu.tracer = Some(Box::new(move |line: &str| {
+            sink.borrow_mut().push(line.to_string())
+        }));
+
+        cpu.step().unwrap();
+        for _ in 0..2 {
+            cpu.tick().unwrap();
+        }
+
+        let lines = lines.borrow();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("C000  E8        INX "));
+        assert!(lines[1].starts_with("C001  E8        INX "));
+        assert!(lines[1].ends_with("A:00 X:01 Y:00 P:24 SP:FD PPU:  0, 27 CYC:9"));
+    }
+}
@@ -0,0 +1,127 @@
+//! Runs Klaus Dormann's 6502 functional and decimal tests.
+//!
+//! Both are 64 KiB images from github.com/Klaus2m5/6502_65C02_functional_tests.
+//! `6502_functional_test.bin` is the prebuilt one in `bin_files`, assembled
+//! with the default configuration. `6502_decimal_test.bin` has to be built
+//! from `6502_decimal_test.a65` with as65, also with its defaults. Neither
+//! is vendored, so both tests are ignored; copy the images into
+//! `tests/data` and run `cargo test --test dormann -- --ignored`. Each test
+//! ends by jumping to itself, and where it does so tells whether it passed.
+
+use std::fs;
+use std::path::PathBuf;
+
+use open_nes::cpu::{Variant, CPU};
+
+/// Where the functional test starts and where it traps once every test
+/// has passed, for the image built with the default configuration.
+const FUNCTIONAL_START: u16 = 0x0400;
+const FUNCTIONAL_SUCCESS: u16 = 0x3469;
+
+/// Where the decimal test starts and the byte it leaves 0 in on success.
+const DECIMAL_START: u16 = 0x0200;
+const DECIMAL_ERROR: u16 = 0x000B;
+
+/// Far more than either test needs, so a runaway program still stops.
+const MAX_CYCLES: u64 = 200_000_000;
+
+fn load_image(name: &str, start: u16) -> CPU {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name);
+    let image = fs::read(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+    assert_eq!(image.len(), 0x10000, "{} is not a 64 KiB image", name);
+
+    let mut cpu = CPU::new();
+    cpu.variant = Variant::Nmos6502;
+    cpu.load(&image, 0x0000).unwrap();
+    cpu.program_counter = start;
+    cpu
+}
+
+fn register_dump(cpu: &CPU) -> String {
+    format!(
+        "PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{} SP:{:02X} CYC:{}",
+        cpu.program_counter,
+        cpu.register_a,
+        cpu.register_x,
+        cpu.register_y,
+        cpu.status,
+        cpu.stack_pointer,
+        cpu.cycles
+    )
+}
+
+/// Runs until an instruction leaves PC where it was, which is how the
+/// tests trap, and returns that PC. Errors and running out of cycles are
+/// reported with a register dump.
+fn run_until_trap(cpu: &mut CPU, max_cycles: u64) -> Result<u16, String> {
+    while cpu.cycles < max_cycles {
+        let pc = cpu.program_counter;
+        if let Err(err) = cpu.step() {
+            return Err(format!("{} ({})", err, register_dump(cpu)));
+        }
+        if cpu.program_counter == pc {
+            return Ok(pc);
+        }
+    }
+    Err(format!(
+        "no trap after {} cycles ({})",
+        max_cycles,
+        register_dump(cpu)
+    ))
+}
+
+#[test]
+#[ignore = "needs tests/data/6502_functional_test.bin, which is not vendored"]
+fn functional_test() {
+    let mut cpu = load_image("6502_functional_test.bin", FUNCTIONAL_START);
+
+    let trap = run_until_trap(&mut cpu, MAX_CYCLES).unwrap();
+
+    assert!(
+        trap == FUNCTIONAL_SUCCESS,
+        "trapped at 0x{:04X}: {}",
+        trap,
+        register_dump(&cpu)
+    );
+}
+
+#[test]
+#[ignore = "needs tests/data/6502_decimal_test.bin, which is not vendored"]
+fn decimal_test() {
+    let mut cpu = load_image("6502_decimal_test.bin", DECIMAL_START);
+
+    let trap = run_until_trap(&mut cpu, MAX_CYCLES).unwrap();
+
+    assert!(
+        cpu.mem_read(DECIMAL_ERROR) == 0,
+        "trapped at 0x{:04X} with ERROR set: {}",
+        trap,
+        register_dump(&cpu)
+    );
+}
+
+#[test]
+fn trap_is_the_instruction_that_jumps_to_itself() {
+    // LDX #5; loop: DEX; BNE loop; JMP *
+    let mut cpu = CPU::new();
+    cpu.load(&[0xa2, 0x05, 0xca, 0xd0, 0xfd, 0x4c, 0x05, 0x04], 0x0400)
+        .unwrap();
+    cpu.program_counter = 0x0400;
+
+    assert_eq!(run_until_trap(&mut cpu, 1_000), Ok(0x0405));
+    assert_eq!(cpu.register_x, 0);
+}
+
+#[test]
+fn trap_detection_gives_up_after_max_cycles() {
+    // loop: INX; JMP loop
+    let mut cpu = CPU::new();
+    cpu.load(&[0xe8, 0x4c, 0x00, 0x04], 0x0400).unwrap();
+    cpu.program_counter = 0x0400;
+
+    let err = run_until_trap(&mut cpu, 100).unwrap_err();
+
+    assert!(err.starts_with("no trap after 100 cycles"), "{}", err);
+}
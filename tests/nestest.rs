@@ -0,0 +1,186 @@
+//! Runs nestest.nes in automation mode against its golden log.
+//!
+//! Starting nestest at 0xC000 instead of its reset vector runs every test
+//! without a PPU. Each instruction's trace line must match the matching
+//! line of `nestest.log`, and at the end 0x02 and 0x03 hold the result
+//! codes of the official and unofficial opcode tests, 0 if all passed.
+//!
+//! The ROM and log, from Kevin Horton's nestest, are not in the repo, so
+//! the comparison is ignored by default. With both copied into
+//! `tests/data`, run it with `cargo test --test nestest -- --ignored`.
+
+use std::fs;
+use std::path::PathBuf;
+
+use open_nes::bus::{Bus, NesBus};
+use open_nes::cpu::CPU;
+use open_nes::trace::trace;
+
+const AUTOMATION_START: u16 = 0xC000;
+const OFFICIAL_RESULT: u16 = 0x0002;
+const UNOFFICIAL_RESULT: u16 = 0x0003;
+
+/// Golden lines shown before the first mismatch.
+const CONTEXT_LINES: usize = 5;
+
+/// The register fields of a trace line, in order, after the disassembly.
+const REGISTER_FIELDS: [&str; 7] = ["A", "X", "Y", "P", "SP", "PPU", "CYC"];
+
+/// The column the register fields start at.
+const REGISTERS_COLUMN: usize = 48;
+
+fn data_file(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name);
+    fs::read(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err))
+}
+
+/// Returns the PRG ROM of an iNES file, skipping the trainer if present.
+fn prg_rom(ines: &[u8]) -> Vec<u8> {
+    assert_eq!(&ines[0..4], b"NES\x1A", "not an iNES file");
+    let banks = ines[4] as usize;
+    let start = if ines[6] & 0b0000_0100 != 0 {
+        16 + 512
+    } else {
+        16
+    };
+    ines[start..start + banks * 0x4000].to_vec()
+}
+
+/// Splits a trace line into named fields so mismatches can be reported
+/// one field at a time.
+fn fields(line: &str) -> Vec<(&'static str, String)> {
+    let column = |range: std::ops::Range<usize>| line.get(range).unwrap_or("").trim().to_string();
+    let mut fields = vec![
+        ("PC", column(0..4)),
+        ("bytes", column(6..15)),
+        ("instruction", column(15..REGISTERS_COLUMN)),
+    ];
+
+    let mut rest = line.get(REGISTERS_COLUMN..).unwrap_or("");
+    for (i, name) in REGISTER_FIELDS.iter().enumerate() {
+        let key = format!("{}:", name);
+        let Some(start) = rest.find(&key) else {
+            fields.push((name, String::new()));
+            continue;
+        };
+        rest = &rest[start + key.len()..];
+        let end = REGISTER_FIELDS
+            .get(i + 1)
+            .and_then(|next| rest.find(&format!(" {}:", next)))
+            .unwrap_or(rest.len());
+        fields.push((name, rest[..end].trim().to_string()));
+        rest = &rest[end..];
+    }
+    fields
+}
+
+/// Describes how `actual` differs from `expected`, one line per field.
+fn field_diffs(expected: &str, actual: &str) -> Vec<String> {
+    fields(expected)
+        .into_iter()
+        .zip(fields(actual))
+        .filter(|((_, expected), (_, actual))| expected != actual)
+        .map(|((name, expected), (_, actual))| {
+            format!("{} differs: expected {} got {}", name, expected, actual)
+        })
+        .collect()
+}
+
+/// Reports the first line where the trace left the golden log, with the
+/// lines before it for context.
+fn mismatch_report(golden: &[&str], index: usize, actual: &str) -> String {
+    let mut report = format!("trace differs from nestest.log at line {}:\n", index + 1);
+    for line in &golden[index.saturating_sub(CONTEXT_LINES)..index] {
+        report.push_str(&format!("    {}\n", line));
+    }
+    report.push_str(&format!("  - {}\n", golden[index]));
+    report.push_str(&format!("  + {}\n", actual));
+    for diff in field_diffs(golden[index], actual) {
+        report.push_str(&format!("  {}\n", diff));
+    }
+    report
+}
+
+#[test]
+#[ignore = "needs tests/data/nestest.nes and nestest.log, which are not vendored"]
+fn nestest_matches_golden_log() {
+    let rom = data_file("nestest.nes");
+    let log = data_file("nestest.log");
+    let log = String::from_utf8(log).expect("nestest.log is not UTF-8");
+    let golden: Vec<&str> = log.lines().collect();
+
+    let mut cpu = CPU::with_bus(NesBus::new(prg_rom(&rom)));
+    cpu.reset();
+    cpu.program_counter = AUTOMATION_START;
+
+    for (index, expected) in golden.iter().enumerate() {
+        let actual = trace(&cpu);
+        if actual.trim_end() != expected.trim_end() {
+            panic!("{}", mismatch_report(&golden, index, &actual));
+        }
+        if let Err(err) = cpu.step() {
+            panic!("line {}: {}\n  {}", index + 1, err, expected);
+        }
+    }
+
+    let official = cpu.bus.peek(OFFICIAL_RESULT);
+    let unofficial = cpu.bus.peek(UNOFFICIAL_RESULT);
+    assert!(
+        official == 0 && unofficial == 0,
+        "nestest reported failures: 0x02 = 0x{:02X}, 0x03 = 0x{:02X}",
+        official,
+        unofficial
+    );
+}
+
+#[test]
+fn field_diffs_name_each_differing_field() {
+    let expected =
+        "C72A  D0 E0     BNE $C70C                       A:00 X:00 Y:00 P:24 SP:FB PPU:  3, 55 CYC:329";
+    let actual =
+        "C72A  D0 E0     BNE $C70C                       A:00 X:00 Y:00 P:26 SP:FB PPU:  3, 58 CYC:330";
+
+    assert_eq!(
+        field_diffs(expected, actual),
+        vec![
+            "P differs: expected 24 got 26",
+            "PPU differs: expected 3, 55 got 3, 58",
+            "CYC differs: expected 329 got 330",
+        ]
+    );
+}
+
+#[test]
+fn field_diffs_compare_the_disassembly() {
+    let expected =
+        "C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 30 CYC:10";
+    let actual =
+        "C5F5  A0 00     LDY #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 30 CYC:10";
+
+    assert_eq!(
+        field_diffs(expected, actual),
+        vec![
+            "bytes differs: expected A2 00 got A0 00",
+            "instruction differs: expected LDX #$00 got LDY #$00",
+        ]
+    );
+}
+
+#[test]
+fn mismatch_report_shows_context_before_the_first_difference() {
+    let golden = [
+        "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7",
+        "C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 30 CYC:10",
+    ];
+    let actual =
+        "C5F5  A2 00     LDX #$00                        A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 33 CYC:11";
+
+    let report = mismatch_report(&golden, 1, actual);
+
+    assert!(report.starts_with("trace differs from nestest.log at line 2:\n"));
+    assert!(report.contains(&format!("    {}\n", golden[0])));
+    assert!(report.contains(&format!("  - {}\n  + {}\n", golden[1], actual)));
+    assert!(report.ends_with("  CYC differs: expected 10 got 11\n"));
+}
@@ -0,0 +1,179 @@
+//! Runs single-step cases in the format of Tom Harte's ProcessorTests
+//! suite for the NMOS 6502.
+//!
+//! Each `<opcode>.json` file holds cases that set up the registers and
+//! RAM, run one instruction and list the state and bus cycles it must end
+//! with. The upstream suite is not vendored: set `PROCESSOR_TESTS_DIR` to
+//! the `6502/v1` directory of a checkout to run its 10,000 cases for every
+//! opcode. Without it, the few hand-written cases in
+//! `tests/data/handwritten` run instead, which only exercise the harness.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use open_nes::bus::{Bus, BusAccess, Ram, RecordingBus};
+use open_nes::cpu::{UnstableOpcodes, Variant, CPU};
+use open_nes::opcode::{Mnemonic, Opcode};
+use open_nes::status::Status;
+use serde::Deserialize;
+
+/// Reports at most this many failing cases in full.
+const MAX_REPORTED: usize = 20;
+
+#[derive(Deserialize)]
+struct TestCase {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Deserialize)]
+struct State {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+fn suite_dir() -> PathBuf {
+    match env::var_os("PROCESSOR_TESTS_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/handwritten"),
+    }
+}
+
+fn test_files() -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(suite_dir())
+        .expect("ProcessorTests directory is missing")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Runs one case and returns a line for every way the CPU disagreed with it.
+fn run_case(case: &TestCase) -> Vec<String> {
+    let mut cpu = CPU::with_bus(RecordingBus::new(Ram::new()));
+    cpu.variant = Variant::Nmos6502;
+    cpu.unstable_opcodes = UnstableOpcodes::Emulate { magic: 0xee };
+    cpu.program_counter = case.initial.pc;
+    cpu.stack_pointer = case.initial.s;
+    cpu.register_a = case.initial.a;
+    cpu.register_x = case.initial.x;
+    cpu.register_y = case.initial.y;
+    cpu.status = Status::from_bits_retain(case.initial.p);
+    for &(addr, value) in &case.initial.ram {
+        cpu.bus.inner.write(addr, value);
+    }
+
+    if let Err(err) = cpu.step() {
+        return vec![format!("step failed: {}", err)];
+    }
+
+    let mut diffs = Vec::new();
+    let expected = &case.expected;
+    let registers = [
+        ("pc", cpu.program_counter, expected.pc),
+        ("s", cpu.stack_pointer as u16, expected.s as u16),
+        ("a", cpu.register_a as u16, expected.a as u16),
+        ("x", cpu.register_x as u16, expected.x as u16),
+        ("y", cpu.register_y as u16, expected.y as u16),
+        ("p", cpu.status.bits() as u16, expected.p as u16),
+    ];
+    for (name, actual, expected) in registers {
+        if actual != expected {
+            diffs.push(format!(
+                "{}: expected 0x{:02X}, got 0x{:02X}",
+                name, expected, actual
+            ));
+        }
+    }
+    for &(addr, value) in &expected.ram {
+        let actual = cpu.bus.peek(addr);
+        if actual != value {
+            diffs.push(format!(
+                "ram[0x{:04X}]: expected 0x{:02X}, got 0x{:02X}",
+                addr, value, actual
+            ));
+        }
+    }
+
+    let accesses: Vec<(u16, u8, &str)> = cpu
+        .bus
+        .accesses
+        .iter()
+        .map(|access| match *access {
+            BusAccess::Read(addr, value) => (addr, value, "read"),
+            BusAccess::Write(addr, value) => (addr, value, "write"),
+        })
+        .collect();
+    let cycles: Vec<(u16, u8, &str)> = case
+        .cycles
+        .iter()
+        .map(|(addr, value, kind)| (*addr, *value, kind.as_str()))
+        .collect();
+    if accesses != cycles {
+        diffs.push(format!("cycles: expected {:?}, got {:?}", cycles, accesses));
+    }
+    diffs
+}
+
+#[test]
+fn processor_tests_6502() {
+    let files = test_files();
+    assert!(
+        !files.is_empty(),
+        "no ProcessorTests files in {:?}",
+        suite_dir()
+    );
+
+    let mut cases = 0;
+    let mut failures = Vec::new();
+    for path in files {
+        let code = path
+            .file_stem()
+            .and_then(|stem| u8::from_str_radix(&stem.to_string_lossy(), 16).ok());
+        // KIL opcodes lock the CPU up rather than finishing an instruction.
+        if code
+            .is_some_and(|code| Opcode::decode(code, Variant::Nmos6502).mnemonic == Mnemonic::JAM)
+        {
+            continue;
+        }
+
+        let json = fs::read_to_string(&path).unwrap();
+        let file_cases: Vec<TestCase> =
+            serde_json::from_str(&json).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        for case in &file_cases {
+            cases += 1;
+            let diffs = run_case(case);
+            if !diffs.is_empty() {
+                failures.push(format!(
+                    "{} \"{}\"\n  {}",
+                    path.display(),
+                    case.name,
+                    diffs.join("\n  ")
+                ));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} cases failed:\n{}",
+        failures.len(),
+        cases,
+        failures
+            .iter()
+            .take(MAX_REPORTED)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+}